@@ -0,0 +1,8846 @@
+//! Merges ZIP/NPZ archives recompressed or aligned and stacks NPY arrays
+//!
+//! # Installation
+//!
+//! ```sh
+//! cargo install rezip
+//! ```
+//!
+//! # Command-line Interface
+//!
+//! ```text
+//! rezip 0.1.3
+//! Rouven Spreckels <rs@qu1x.dev>
+//! Merges ZIP/NPZ archives recompressed or aligned and stacks NPY arrays
+//!
+//! Options accepting <[glob=]value> pairs use the given values for matching file
+//! names in input ZIP archives. Matches of former pairs are superseded by matches
+//! of latter pairs. Omitting [glob=] by only passing a value assumes the * glob
+//! pattern matching all file names whereas an empty glob pattern matches no file
+//! names. An empty value disables the option for the file names matching the glob
+//! pattern. Passing a single pair with an empty glob pattern and an empty value,
+//! that is a = only, disables an option with default values entirely as in
+//! --recompress = whereas passing no pairs as in --recompress keeps assuming the
+//! default values.
+//!
+//! USAGE:
+//!     rezip [OPTIONS] [--] [glob]...
+//!
+//! ARGS:
+//!     <glob>...
+//!             Merges or checks input ZIP archives.
+//!
+//!             Stacks identically named files in different input ZIP archives in
+//!             the order given by parsing supported file formats like NPY (NumPy
+//!             array file). Otherwise, only the file in the last given input ZIP
+//!             archive is merged into the output ZIP archive.
+//!
+//!             Input TAR archives with .tar, .tar.gz, .tgz, .tar.zst, or .tzst
+//!             extensions are read like input ZIP archives of uncompressed files
+//!             with their names, sizes, modification times, and Unix modes. Gzipped
+//!             or Zstandard-compressed ones are decompressed into a temporary file
+//!             first, so their files are read in any order as stacking requires.
+//!             Other than regular files and directories are skipped.
+//!
+//!             Reads further globs from list files given as @path, one glob per
+//!             line with leading and trailing whitespace trimmed, ignoring blank
+//!             lines and lines starting with #. Globs in list files are taken
+//!             verbatim, so paths with spaces need no quoting, whereas glob
+//!             metacharacters are matched literally in brackets as in [*]. Relative
+//!             globs are relative to the working directory, not to the list file.
+//!
+//! OPTIONS:
+//!         --input-order <order>
+//!             Sorts input ZIP archives.
+//!
+//!             Sorts the paths of input ZIP archives matched by all globs as given,
+//!             by name, in natural order comparing runs of digits by their numeric
+//!             values, or by their last modification times before indexing them.
+//!             Sets the order of stacking and the priority of merging, whereas
+//!             --stack-order re-sorts identically named files to be stacked
+//!             afterwards. Ties keep the given order.
+//!
+//!             [default: asis]
+//!             [possible values: asis, name, natural, mtime]
+//!
+//!     -o, --output <path>
+//!             Writes output ZIP archive.
+//!
+//!             With no output ZIP archive, checks if files in input ZIP archives
+//!             are as requested according to --recompress and --align. Recompress
+//!             levels are checked exactly for bzip2 and on a best effort basis for
+//!             zstd by inferring them from the window size, which only tells levels
+//!             with different default window sizes apart and nothing if the window
+//!             size is the content size. The levels of deflated files are not
+//!             recoverable and --merge matches are not checked.
+//!
+//!             Writes a temporary file next to the output ZIP archive named like it
+//!             with a .tmp- extension and a random suffix, which is renamed to the
+//!             output ZIP archive when finished and removed on failure. Volumes of
+//!             --split-size are written alike.
+//!
+//!             Writes one output ZIP archive per input ZIP archive if the path
+//!             contains {stem}, as in --output 'out/{stem}.zip', replacing it with
+//!             the file stem of the input ZIP archive. Runs once per input ZIP
+//!             archive, so files are neither stacked nor merged across input ZIP
+//!             archives. Fails if two input ZIP archives share a file stem.
+//!
+//!         --tmpdir <path>
+//!             Writes temporary output ZIP archive into directory.
+//!
+//!             Writes the temporary file of --output into the given directory
+//!             instead of next to the output ZIP archive, failing early unless both
+//!             are on the same file system for renaming it atomically.
+//!
+//!         --sfx
+//!             Writes output ZIP archive as self-extracting shell script.
+//!
+//!             Prepends a POSIX shell script extracting the output ZIP archive into
+//!             a new temporary directory with unzip and printing its path, and
+//!             makes it executable by its owner. Offsets and --align paddings
+//!             account for the script, whereas ZIP readers skip it as leading data.
+//!             Requires sh, mktemp, and unzip on the extracting system. Tools
+//!             rewriting ZIP archives may drop the script.
+//!
+//!     -x, --extract <dir>
+//!             Extracts files into directory.
+//!
+//!             Merges and stacks files as --output does into a temporary ZIP
+//!             archive next to the given directory and extracts it into the
+//!             directory instead, creating missing directories and restoring Unix
+//!             modes and last modification times of files where available. Fails on
+//!             existing files unless --force is given. Of the options requiring
+//!             --output, only --collision, --strict, --dedup, and --no-dir-entries
+//!             apply.
+//!
+//!         --filter
+//!             Filters ZIP archive from stdin to stdout.
+//!
+//!             Reads a single input ZIP archive from stdin instead of globs and
+//!             writes the output ZIP archive to stdout as --output does, as in
+//!             rezip --filter -r zstd < in.zip > out.zip. As ZIP archives are read
+//!             from their central directory at the end and written by seeking back
+//!             to complete local file headers, both are buffered in temporary
+//!             files, so memory usage stays low while disk usage doubles. Nothing
+//!             is written to stdout on failure. Of the options requiring --output,
+//!             none apply.
+//!
+//!     -l, --list
+//!             Lists files in input ZIP archives.
+//!
+//!             Prints a table per input ZIP archive with the size, method,
+//!             compressed size, compression ratio, last modification time, CRC-32,
+//!             and alignment of each file without extracting it. The alignment is
+//!             the remainder of dividing the start of the file data by the bytes
+//!             requested by --align, being 0 when aligned. Files in input
+//!             directories and TAR archives have no CRC-32 to list before reading
+//!             them.
+//!
+//!         --list-json
+//!             Lists files in input ZIP archives as JSON.
+//!
+//!             Prints one JSON array of objects with the name, method, compressed
+//!             size, uncompressed size, CRC-32 in hexadecimal, ISO 8601 last
+//!             modification time, directory flag, Unix mode, data start, and
+//!             alignment of each file as listed by --list, along with the input ZIP
+//!             archive it is from. Missing values are null. Sizes and offsets
+//!             beyond 2^53 are strings as JSON numbers cannot represent them
+//!             exactly in common parsers.
+//!
+//!         --verify
+//!             Verifies files by decompressing them.
+//!
+//!             Reads each file of the input ZIP archives in full while checking
+//!             them, verifying their CRC-32 if any. Reports each file failing to
+//!             decompress and fails after checking all files if any did.
+//!
+//!         --report-methods
+//!             Reports compression methods of files in input ZIP archives.
+//!
+//!             Prints a histogram of the compression methods of all files without
+//!             checking them against --recompress, with one event per file at -v
+//!             verbosity. Levels are inferred as far as possible when checking,
+//!             exactly for bzip2 and as a range for zstd as in zstd:3-8, whereas
+//!             deflated levels are not recoverable. Directories are skipped.
+//!
+//!     -f, --force
+//!             Writes existing output ZIP archive
+//!
+//!         --update
+//!             Updates existing output ZIP archive.
+//!
+//!             Reads the files of an existing output ZIP archive as if it were
+//!             given as first input ZIP archive, except that identically named
+//!             files in input ZIP archives supersede them instead of being stacked
+//!             with them and that --merge, --rename, --normalize-paths, and
+//!             --prefix are not applied to them. Replaces the existing output ZIP
+//!             archive when finished and rewrites the file of --checksums.
+//!
+//!         --repack-only
+//!             Recompresses or aligns a single input ZIP archive only.
+//!
+//!             Writes the files of exactly one input ZIP archive in their order as
+//!             requested by --recompress and --align, neither stacking nor merging
+//!             them nor adding missing directories. Fails if the globs match more
+//!             than one input ZIP archive, whereas --output with {stem} repacks
+//!             each of them on its own.
+//!
+//!         --comment-file <path>
+//!             Comments output ZIP archive by file.
+//!
+//!             Reads the comment of the output ZIP archive verbatim from the given
+//!             file, failing if it exceeds the limit of 65535 bytes. Each volume of
+//!             --split-size is commented alike.
+//!
+//!         --append-trailer <path>
+//!             Appends data of file to output ZIP archive.
+//!
+//!             Writes the bytes of the given file verbatim after the end of central
+//!             directory record when finished, as for formats placing metadata
+//!             behind the ZIP archive. ZIP readers ignore trailing data as long as
+//!             they find the record by searching backwards, so fails if the data
+//!             and --comment-file exceed 65535 bytes together, keeping the output
+//!             ZIP archive valid. With --split-size, the data is appended to the
+//!             last volume only. Tools rewriting ZIP archives may drop the data.
+//!
+//!         --touch-output <iso8601|now|source>
+//!             Sets modification time of output ZIP archive.
+//!
+//!             Sets the last modification time of the output ZIP archive itself
+//!             when finished to the given ISO 8601 time as in 2000-01-01T00:00:00Z,
+//!             to the time of this run with now, or with source to the newest last
+//!             modification time of its files, leaving it untouched if there are
+//!             none. Unlike --entry-timestamp-source, this lets build systems
+//!             comparing modification times consider the output ZIP archive as old
+//!             as its contents. With --split-size, only the last volume is touched.
+//!
+//!         --verify-output
+//!             Verifies output ZIP archive before renaming it.
+//!
+//!             Reopens the temporary output ZIP archive when finished and reads
+//!             each file in full, verifying its CRC-32, and the shape of each
+//!             stacked array. Fails instead of renaming it to the output ZIP
+//!             archive if any are wrong. Each volume of --split-size is verified
+//!             alike.
+//!
+//!         --recompute-crc
+//!             Recomputes CRC-32 of files copied as is.
+//!
+//!             Decompresses files whose compressed data is copied without
+//!             recompressing it and fails if their CRC-32 mismatches the one stored
+//!             in the input ZIP archive, detecting its corruption. Otherwise,
+//!             copied files are trusted without reading them twice, which is about
+//!             as slow as reading them once more when compressed and barely slower
+//!             when stored.
+//!
+//!         --index-entry <name>
+//!             Writes sorted list of files into output ZIP archive.
+//!
+//!             Adds a stored file of the given name last, listing the names of all
+//!             other files and directories written to the output ZIP archive sorted
+//!             and separated by LF terminators. With --split-size, it is added to
+//!             the last volume listing the files of all volumes. Fails if the name
+//!             is taken by another file.
+//!
+//!         --continue-on-error
+//!             Skips unreadable input ZIP archives and files.
+//!
+//!             Prints a warning for each input ZIP archive or file which cannot be
+//!             read and excludes it instead of aborting. Still fails after merging
+//!             or checking the remaining ones if any were skipped.
+//!
+//!         --fail-fast
+//!             Stops at the first output ZIP archive failing to be written.
+//!
+//!             Aborts the remaining runs of --output with {stem} as soon as one
+//!             fails. This is the default and overrides --no-fail-fast.
+//!
+//!         --no-fail-fast
+//!             Writes all output ZIP archives before failing.
+//!
+//!             Continues with the remaining runs of --output with {stem} if one
+//!             fails and reports the errors of all failed output ZIP archives
+//!             together when finished, failing if any did. A single output ZIP
+//!             archive is never written partially, so this has no effect otherwise.
+//!
+//!         --allow-duplicate-inputs
+//!             Keeps last of identically named files in an input ZIP archive.
+//!
+//!             Prints a warning for each file in an input ZIP archive named like a
+//!             former file in the same input ZIP archive and keeps the latter
+//!             instead of aborting.
+//!
+//!         --collision <policy>
+//!             Selects among identically named files not stacked.
+//!
+//!             Merges the file of the last or first given input ZIP archive, the
+//!             newest one by last modification time, or the largest one by
+//!             uncompressed size. Ties are resolved in favor of the last given
+//!             input ZIP archive. Stacked files are unaffected. Defaults to the
+//!             last one.
+//!
+//!             [possible values: last, first, newest, largest]
+//!
+//!         --preserve-order
+//!             Orders files as in their input ZIP archives.
+//!
+//!             Writes the files ordered by the input ZIP archive and the position
+//!             in it of the file selected by --collision instead of by the first
+//!             input ZIP archive they are seen in, which interleaves the files of
+//!             different input ZIP archives. Keeps the order of a single input ZIP
+//!             archive exactly. Stacked files are positioned like merged ones,
+//!             since they are stacked from several input ZIP archives. Directories
+//!             added for nested files precede their first file.
+//!
+//!         --rename-collisions
+//!             Keeps identically named files not stacked by numbering their names.
+//!
+//!             Writes the file of each input ZIP archive instead of selecting one
+//!             by --collision, numbering all but the first in the given order of
+//!             input ZIP archives as in data.npy, data.1.npy, and data.2.npy.
+//!             Numbers are inserted before the first extension and skip names of
+//!             other files. Directories are merged as usual. Globs of other options
+//!             match the numbered names.
+//!
+//!         --strict
+//!             Fails on identically named files not stacked.
+//!
+//!             Aborts instead of merging only one of identically named files of
+//!             different input ZIP archives which are neither stacked nor selected
+//!             by an explicitly given --collision policy, preventing the accidental
+//!             loss of shards.
+//!
+//!         --max-open-inputs <count>
+//!             Limits simultaneously open input ZIP archives.
+//!
+//!             Closes the least recently used input ZIP archive when opening
+//!             another one would exceed the given count of at least one. Input ZIP
+//!             archives are opened once for indexing their files and reopened on
+//!             demand while merging, which traverses input directories again and
+//!             decompresses gzipped input TAR archives again. Files of input
+//!             directories are opened one at a time.
+//!
+//!             [default: 64]
+//!
+//!         --follow-links
+//!             Follows symbolic links in input directories.
+//!
+//!             Traverses symbolically linked directories and reads symbolically
+//!             linked files of input directories instead of skipping them. Input
+//!             directories given as symbolic links are always followed. Unlike
+//!             prior versions, symbolic links are skipped by default.
+//!
+//!     -C, --base <dir>
+//!             Names files of input directories relative to base directory.
+//!
+//!             Strips the given directory from the paths of files in input
+//!             directories, as in -C mydir mydir storing mydir/file as file
+//!             instead, whereas the base directory itself is omitted. Fails if a
+//!             file of an input directory is not under the base directory.
+//!
+//!         --split-size <bytes>
+//!             Splits output ZIP archive into volumes.
+//!
+//!             Starts a new volume whenever writing the next file would exceed the
+//!             given bytes. Finished volumes are renamed to .z01, .z02, and so on
+//!             whereas the last volume keeps the output path. Each volume is a
+//!             self-contained ZIP archive as files are never split across volumes.
+//!             A file exceeding the given bytes on its own is written into its own
+//!             volume. The size of a file to write is estimated by its size in the
+//!             input ZIP archive, whereas the size of a stacked array is estimated
+//!             by the total size of the arrays it stacks as it is written as a
+//!             single file.
+//!
+//!         --zip64 <mode>
+//!             Uses ZIP64 extensions.
+//!
+//!             Always writes ZIP64 extended information of files, enabling files
+//!             and output ZIP archives of more than 4 GiB or 65535 files, or never
+//!             writes ZIP64 extensions for tools not supporting them, failing on
+//!             the first file exceeding these limits.
+//!
+//!             [default: always]
+//!             [possible values: always, never]
+//!
+//!         --no-zip64
+//!             Writes no ZIP64 extensions.
+//!
+//!             Is an alias of --zip64 never.
+//!
+//!         --checksums <algorithm>
+//!             Writes checksums of files in output ZIP archive.
+//!
+//!             Writes the digests of the uncompressed files in the order they are
+//!             written to the output ZIP archive into a sidecar file named after
+//!             the output ZIP archive with the algorithm as additional extension,
+//!             each line listing a digest and a name separated by two spaces as
+//!             expected by sha256sum and b3sum. Stacked arrays are digested as
+//!             written, files of --cache-dir while hashing their key. Files copied
+//!             without recompression are read once more to digest their
+//!             uncompressed data.
+//!
+//!             [possible values: sha256, blake3]
+//!
+//!         --diff-against <path>
+//!             Reports changes of files compared to previous ZIP archive.
+//!
+//!             Compares the uncompressed files of the output ZIP archive when
+//!             finished with identically named ones in the given ZIP archive by
+//!             their SHA-256 digests, reporting each file as added, removed,
+//!             modified, or unchanged with -v verbosity and the counts of each in
+//!             total. The previous ZIP archive is read before writing, so it may be
+//!             the output ZIP archive itself when overwritten with --force. The
+//!             output ZIP archive is not altered.
+//!
+//!         --dedup <mode>
+//!             Detects files of identical content.
+//!
+//!             Digests the uncompressed data of each file to write with SHA-256 and
+//!             either drops files identical to a formerly written one or only
+//!             reports them and the bytes dropping them would save. Stacked arrays
+//!             and directories are never deduplicated. Costs reading each file once
+//!             more and keeping one digest and name per written file in memory.
+//!
+//!             [possible values: drop, report]
+//!
+//!         --content-address
+//!             Names files by their content.
+//!
+//!             Renames each file to the hexadecimal BLAKE3 hash of its uncompressed
+//!             data followed by the extension of its name, dropping files of
+//!             identical content and directories. Stacked files are hashed after
+//!             stacking. Globs of other options still match the original names.
+//!             Costs reading each file once more and writing stacked files to
+//!             temporary files first.
+//!
+//!         --ignore-case
+//!             Matches globs of options case-insensitively.
+//!
+//!             Matches the globs of --merge, --prefix, --recompress, --align, and
+//!             --stack regardless of case as in --align '*.so=4096' matching
+//!             lib.SO. Globs of input ZIP archives are unaffected.
+//!
+//!         --match-basename
+//!             Matches globs without slashes against file names only.
+//!
+//!             Matches the globs of --merge, --prefix, --recompress, --align, and
+//!             --stack not containing a slash against the last component of paths
+//!             as in --align 'foo.so=4096' matching lib/foo.so instead of requiring
+//!             '*/foo.so'. Globs containing a slash still match whole paths.
+//!
+//!         --rules <path>
+//!             Reads <[glob=]value> pairs from TOML file.
+//!
+//!             Reads arrays of pairs as passed on the command line from the
+//!             recompress, align, stack, and merge keys of the given TOML file, as
+//!             in recompress = ['*.npy=zstd:3', '*.so=stored']. Pairs of the
+//!             command line are appended to the ones of the file, so their matches
+//!             supersede the file's, whereas options left at their default values
+//!             take the pairs of the file only. Fails on other keys.
+//!
+//!     -m, --merge <[glob=]name>
+//!             Merges files as if they were in ZIP archives.
+//!
+//!             Merges files as if they were in different ZIP archives and renames
+//!             them to the given names. With empty names, keeps original names,
+//!             effectively creating a ZIP archive from input files. Files in input
+//!             ZIP archives matching the globs are renamed alike. In names, {} is
+//!             replaced by the file name of the matching file, which is appended to
+//!             names ending with a slash as in --merge 'old/*.bin=new/'.
+//!
+//!             Files keep their permissions and last modification times unless
+//!             overridden by --merge-mode and --merge-time.
+//!
+//!         --glob-as-group
+//!             Merges files matched by each input glob as one group.
+//!
+//!             Merges the regular files matched by an input glob as if --merge
+//!             renamed them to a name derived from the glob, so they are stacked
+//!             together as in --glob-as-group 'part-*.npy' stacking part-0.npy,
+//!             part-1.npy, and so on as part.npy. The name is the file name of the
+//!             glob up to its first metacharacter trimmed of trailing -, _, and .
+//!             characters, followed by its extension after the last metacharacter.
+//!             Fails if the name is empty, as for '*.npy'. Matched input ZIP
+//!             archives are merged as files too, whereas matches of --merge take
+//!             precedence.
+//!
+//!         --merge-mode <[glob=]mode>
+//!             Sets permissions of files of input directories.
+//!
+//!             Overrides the Unix permissions of files and directories of input
+//!             directories and of files of --merge matching the globs with the
+//!             given octal mode as in --merge-mode 'bin/*=0755'. Globs match the
+//!             names before --merge renames.
+//!
+//!         --merge-time <[glob=]seconds>
+//!             Sets modification time of files of input directories.
+//!
+//!             Overrides the last modification times of files and directories of
+//!             input directories and of files of --merge matching the globs with
+//!             the given Unix timestamp in seconds as in --merge-time 315532800 for
+//!             reproducible output ZIP archives. Globs match the names before
+//!             --merge renames.
+//!
+//!         --entry-timestamp-source <source|now|fixed=<iso8601>|max>
+//!             Sets modification times of output files.
+//!
+//!             Writes files with the last modification time of their source file,
+//!             the time of this run with now, the given ISO 8601 time as in
+//!             fixed=1980-01-01T00:00:00Z, or with max the newest time of the files
+//!             stacked into them. Stacked files take the time of the last file
+//!             stacked into them with source, whereas fixed or max make them
+//!             independent of the stack order. Directories are not affected.
+//!
+//!             [default: source]
+//!
+//!         --rename <regex=replacement>
+//!             Renames files by regular expression.
+//!
+//!             Substitutes all matches of the regular expression in the names of
+//!             files by the replacement, which may refer to capture groups as in $1
+//!             or ${name}. Splits at the first = not escaped as \= in the regular
+//!             expression, so the replacement may contain = as in --rename
+//!             'a\.txt=k=v.txt'. Renames are applied in the given order after
+//!             --merge renames. Renamed files colliding with others are merged or
+//!             stacked alike. Renames resulting in empty or absolute names are
+//!             invalid.
+//!
+//!         --normalize-paths
+//!             Normalizes names of files.
+//!
+//!             Replaces backslashes with slashes, removes empty and . components,
+//!             and ends names of directories with a slash before --merge globs and
+//!             --rename expressions are applied. Names becoming identical are
+//!             merged or stacked like identically named files.
+//!
+//!         --utf8-names
+//!             Decodes names of files as UTF-8.
+//!
+//!             Takes the names of files in input ZIP archives as UTF-8 whenever
+//!             they are valid UTF-8, even if their language encoding flag (bit 11)
+//!             is unset, which many archivers omit. Otherwise, such names are
+//!             decoded as CP437 per specification, mangling UTF-8 ones. Warns about
+//!             names being neither and keeps them decoded as CP437. Output ZIP
+//!             archives always store names as UTF-8 with the flag set for non-ASCII
+//!             ones, whereas ASCII names read the same either way.
+//!
+//!         --strip-components <count>
+//!             Strips leading components of names of files.
+//!
+//!             Removes the given number of leading directories from the names of
+//!             files in input ZIP archives like tar does, as in --strip-components
+//!             1 naming pkg/lib/a.so as lib/a.so. Skips files and directories with
+//!             no more components than stripped, like pkg/ and README, instead of
+//!             keeping them. Applies after --normalize-paths and before --merge
+//!             globs and --rename expressions.
+//!
+//!         --prefix <[glob=]path>
+//!             Prepends path to names of files.
+//!
+//!             Nests the files of input ZIP archives matching the globs under the
+//!             given path as in --prefix 'a.zip=vendor/a' --prefix
+//!             'b.zip=vendor/b', preventing collisions of merged files. The path is
+//!             prepended after --rename expressions are applied.
+//!
+//!         --no-dir-entries
+//!             Omits directories in output ZIP archive.
+//!
+//!             Writes files only, as their names imply the directories containing
+//!             them. Empty directories are lost, including the ones of input
+//!             directories. Otherwise, directories missing in input ZIP archives
+//!             are added for every file and directory nested in them, including the
+//!             ones of --prefix.
+//!
+//!         --dedup-dirs
+//!             Collapses variants of names of directories.
+//!
+//!             Merges directories whose names differ in case or separators only,
+//!             like lib/, Lib/, and lib\ of different input archives, into one
+//!             named as the first one seen. Files keep their names.
+//!
+//!         --min-size <size>
+//!             Excludes files smaller than the given size.
+//!
+//!             Skips files of input ZIP archives while indexing them by their
+//!             uncompressed sizes in bytes or with a binary suffix as in 4KiB,
+//!             16MiB, 1GiB, or 2TiB. Identically named files are excluded one by
+//!             one, leaving fewer or no files to be stacked. Directories are
+//!             unaffected.
+//!
+//!         --max-size <size>
+//!             Excludes files larger than the given size.
+//!
+//!             Skips files like --min-size does, as in --max-size 100MiB.
+//!
+//!         --max-name-length <bytes>
+//!             Fails on names of files longer than the given bytes.
+//!
+//!             Checks the names of files in input ZIP archives while indexing them
+//!             before they are renamed, guarding against pathological input ZIP
+//!             archives.
+//!
+//!         --max-depth <depth>
+//!             Fails on names of files nested deeper than the given depth.
+//!
+//!             Checks the names of files like --max-name-length does by counting
+//!             their components, as in 3 for a/b/c.txt and a/b/c/. Fails on
+//!             absolute names and names with .. components regardless of their
+//!             depth.
+//!
+//!         --max-entries <files>
+//!             Fails on more files in output ZIP archive than given.
+//!
+//!             Counts the files and directories to be written after indexing all
+//!             input ZIP archives before reading any of them, including the
+//!             directories added for nested files.
+//!
+//!     -r, --recompress <[glob=]method>
+//!             Writes files recompressed.
+//!
+//!             Supported methods are stored (uncompressed), deflated[:0-9] (most
+//!             common) with 6 as default level, bzip2[:1-9] (high ratio) with 6 as
+//!             default level, and zstd[:1-21] (modern) with 3 as default level.
+//!             Files already compressed with the requested method are copied
+//!             without recompression unless a level is given, they are stacked,
+//!             --entry-timestamp-source changes their modification times, or
+//!             --zip64 always writes ZIP64 extended information of files as by
+//!             default.
+//!
+//!             Levels of bzip2 select block sizes of 100 to 900 kB. A work factor
+//!             of 1 to 250 may follow as in bzip2:9,100, 30 by default, bounding
+//!             the effort spent sorting repetitive data before falling back to a
+//!             slower but steady algorithm. It affects the speed only, not the
+//!             compressed data. Files are compressed into a temporary file first
+//!             and then copied like with --zstd-long.
+//!
+//!             The keep method keeps the original methods of matching files as in
+//!             --recompress zstd --recompress '*.jpg=keep', like an empty method as
+//!             in '*.jpg='. Files matching no globs keep their original methods as
+//!             well, whereas without this option all files are stored. An empty
+//!             glob and method as in --recompress = matches no files, keeping the
+//!             original methods of all.
+//!
+//!             The auto method selects stored for files already compressed by
+//!             extension like .jpg, .png, .zip, or .gz, for empty files, and for
+//!             files of more than 7.5 bits of entropy per byte in their first 64
+//!             KiB, and zstd otherwise. Methods of other matching globs take
+//!             precedence over auto regardless of their order.
+//!
+//!             Globs qualified by globs of input ZIP archives as in --recompress
+//!             'vendor.zip:*.so=stored' match files of matching input ZIP archives
+//!             only, as they do for --precompress and --align. Stacked files match
+//!             no qualified globs. Qualifiers end at the first colon preceded by an
+//!             archive extension like .zip, .npz, .tar, or .tgz, so other colons
+//!             match literally as in 'e/b:c.txt', whereas [:] matches a literal
+//!             colon after such an extension as in 'a.zip[:]b.txt'.
+//!
+//!             [default: stored]
+//!
+//!         --precompress <[glob=]codec>
+//!             Writes files precompressed and stored.
+//!
+//!             Compresses the data of files with the given codec and writes the
+//!             compressed stream stored, so it is aligned like other stored files
+//!             but, unlike with --recompress, not decompressed by ZIP readers. The
+//!             only supported codec is xz[:0-9] with 6 as default level. Names are
+//!             kept, so consumers must know which files are wrapped, like by the xz
+//!             magic number. Checksums digest the compressed stream as written.
+//!
+//!         --aligned-compressed <codec>
+//!             Writes compressed files precompressed, stored, and aligned.
+//!
+//!             Compresses the data of files to be compressed and aligned by
+//!             --recompress and --align with the given codec instead and writes the
+//!             compressed stream stored and aligned, trading compatibility for both
+//!             alignment and size reduction. The only supported codec is
+//!             zstd[:1-21] with 3 as default level. The stream ends with a 16-byte
+//!             trailer of the decompressed length as little-endian 64-bit integer,
+//!             the codec name zstd, and the magic RZIP. ZIP readers extract the
+//!             stream as is, so a companion reader checks whether a file ends with
+//!             RZIP, takes the codec name from the 4 bytes before, decompresses the
+//!             stream without its trailer, and compares the decompressed length
+//!             with the first 8 bytes of the trailer. Files precompressed by
+//!             --precompress are not affected.
+//!
+//!         --keep-extra-fields
+//!             Keeps extra fields of files.
+//!
+//!             Keeps extra fields of files in input ZIP archives like
+//!             high-resolution timestamps and Unix ownership. Files with extra
+//!             fields are recompressed rather than copied as is, since only
+//!             recompression allows writing extra fields. Extra fields which are
+//!             rewritten, like ZIP64 sizes and alignment padding, or invalidated by
+//!             renaming, like Unicode paths, are dropped. Extra fields describing
+//!             the compressed data itself do not survive recompression. Stacked
+//!             arrays and directories have no extra fields.
+//!
+//!         --owner <uid>
+//!             Sets Unix owner of files.
+//!
+//!             Writes the Info-ZIP Unix extra field with the given UID. The GID is
+//!             taken from --group, the file with --preserve-owner, or is 0
+//!             otherwise. Files with ownership are recompressed rather than copied
+//!             as is. Directories have no extra fields and hence no ownership.
+//!
+//!         --group <gid>
+//!             Sets Unix group of files.
+//!
+//!             Writes the Info-ZIP Unix extra field with the given GID like --owner
+//!             does with the UID.
+//!
+//!         --preserve-owner
+//!             Preserves Unix owner and group of files.
+//!
+//!             Takes UID and GID from the metadata of input files, the headers of
+//!             input TAR archives, or the Info-ZIP Unix extra fields of files in
+//!             input ZIP archives, unless overridden by --owner or --group. Input
+//!             files have no ownership on non-Unix hosts where only --owner and
+//!             --group apply.
+//!
+//!     -a, --align <[glob=]bytes>
+//!             Aligns uncompressed files.
+//!
+//!             Aligns uncompressed files in ZIP archives by padding local file
+//!             headers to enable memory-mapping, SIMD instruction extensions like
+//!             AVX-512, and dynamic loading of shared objects.
+//!
+//!             [default: 64 *.so=4096]
+//!
+//!         --align-all
+//!             Aligns compressed files too.
+//!
+//!             Aligns the start of the compressed data of compressed files as well
+//!             for loaders mapping it into memory before decompressing it. The
+//!             decompressed data itself is not aligned. Checks the alignment of
+//!             compressed files too with no output ZIP archive.
+//!
+//!             [aliases: align-compressed]
+//!
+//!         --align-arbitrary
+//!             Allows alignments other than powers of two.
+//!
+//!             Accepts any --align bytes from 1 to 65535, like 48 or 96 for loaders
+//!             of packed records, instead of powers of two only.
+//!
+//!     -s, --stack <[glob=]axis>
+//!             Stacks arrays along axis.
+//!
+//!             Stacks identically named NPY files case-insensitively by extension.
+//!             Members of input NPZ archives are stacked with their counterparts in
+//!             other input NPZ archives, resulting in an output NPZ archive of
+//!             stacked members. Globs match NPY files with and without extension,
+//!             so members of input NPZ archives match by their array names as in
+//!             --stack 'images=0' --stack 'labels=1', including their paths as in
+//!             --stack 'weights/*=0'. Identically named NPY files matching no globs
+//!             are merged as selected by --collision, the one of the last given
+//!             input NPZ archive by default, since passing globs replaces the
+//!             default of stacking all along axis 0. Supports booleans, integers,
+//!             and floats of single and double precision, and of half precision if
+//!             built with the half feature. Gzipped NPY files with .npy.gz
+//!             extensions are decompressed before stacking and written decompressed
+//!             without their .gz extensions unless --stack-gzip is given.
+//!
+//!             Stacks identically named CSV files case-insensitively by extension
+//!             along axis 0 only by concatenating their records. Rows are rewritten
+//!             with minimal quoting and LF terminators as they are parsed and not
+//!             copied verbatim.
+//!
+//!             Stacks identically named JSON files case-insensitively by extension
+//!             along axis 0 only by concatenating the elements of their top-level
+//!             arrays or by merging the keys of their top-level objects as selected
+//!             by --json-merge. Values are rewritten compactly as they are parsed.
+//!
+//!             Stacks identically named Parquet files case-insensitively by
+//!             extension along axis 0 only by concatenating their row groups if
+//!             built with the parquet feature. Requires the schemas of stacked
+//!             Parquet files to have identical fields and keeps the metadata of the
+//!             first one. Each input is spilled to a temporary file for random
+//!             access, while the written Parquet file is buffered as a whole,
+//!             uncompressed, in memory.
+//!
+//!             One stacked array at a time must fit into memory before it is
+//!             written to the output ZIP archive, along with one of its input
+//!             arrays when stacking along axis 0, as each is read into the
+//!             preallocated stacked array in turn, or with all of them otherwise.
+//!
+//!             [default: 0]
+//!
+//!         --stack-broadcast
+//!             Broadcasts lower-dimensional arrays before stacking.
+//!
+//!             Broadcasts arrays of fewer dimensions, like scalars, to the common
+//!             shape of the arrays of the most dimensions with a length of one
+//!             along the stack axis. Broadcasting follows NumPy by prepending axes
+//!             of length one and repeating axes of length one. Arrays of the most
+//!             dimensions must agree in all but the stack axis for the common shape
+//!             to be unambiguous.
+//!
+//!         --flatten-stack
+//!             Flattens arrays before stacking.
+//!
+//!             Ravels each array to be stacked in C order to one dimension and
+//!             concatenates them into one-dimensional arrays regardless of the
+//!             stack axis, like per-shard scalars into an array of them. The shapes
+//!             of the arrays are lost, so their lengths may differ.
+//!
+//!         --stack-gzip
+//!             Gzips stacked NPY files again.
+//!
+//!             Writes stacked .npy.gz files gzipped again keeping their names
+//!             instead of decompressed without their .gz extensions.
+//!
+//!         --stack-dtype <dtype>
+//!             Stacks NPY files cast to a common data-type.
+//!
+//!             Reads the arrays to be stacked as the given data-type instead of
+//!             requiring them to agree, as in --stack-dtype f64 stacking arrays of
+//!             single and double precision. Fails unless the data-type of each
+//!             array is represented by the given one without loss, like integers of
+//!             up to 16 bits by single precision and of up to 32 bits by double
+//!             precision. Half precision, available as f16 if built with the half
+//!             feature, is cast to single and double precision, whereas nothing is
+//!             cast to it.
+//!
+//!             [possible values: bool, u8, i8, u16, i16, u32, i32, u64, i64, f32,
+//!             f64]
+//!
+//!         --stack-cast <dtype->dtype>
+//!             Stacks NPY files of differing data-types by listed casts.
+//!
+//!             Allows the given casts only, as in --stack-cast 'i32->i64,f32->f64',
+//!             instead of requiring the arrays to be stacked to agree in data-type.
+//!             Casts them to the one data-type all others are listed to be cast to,
+//!             failing with the first array lacking a listed cast otherwise. Arrays
+//!             agreeing in data-type are stacked as is. Only casts representing
+//!             each value without loss are accepted, whereas narrowing casts, like
+//!             i64->i32 or i32->f32, are never applied.
+//!
+//!         --npy-order <order>
+//!             Writes stacked NPY files in memory order.
+//!
+//!             Writes stacked arrays in C (row-major) or Fortran (column-major)
+//!             order. Arrays of either order are read and stacked by their logical
+//!             indices, so inputs may mix orders, while the order of the output is
+//!             explicit instead of following the inputs.
+//!
+//!             [default: c]
+//!             [possible values: c, f]
+//!
+//!         --raw-spec <glob=path>
+//!             Reads raw files as NPY files described by JSON file.
+//!
+//!             Reads the files matching the globs as headerless arrays described by
+//!             the given JSON file, as in --raw-spec '*.bin=spec.json' with
+//!             {"shape": [480, 640], "dtype": "u8"} and optional "fortran_order":
+//!             true, and writes them as NPY files with .npy extensions instead,
+//!             which are stacked like others. The data-type is given by name as for
+//!             --stack-dtype or as NumPy type string like >f4 for big-endian data,
+//!             whereas names assume little-endian data. Fails unless the size of
+//!             each file matches the one described.
+//!
+//!         --stack-cmd <glob=command>
+//!             Stacks files by external command.
+//!
+//!             Stacks identically named files matching the globs of any format by
+//!             running the command split at whitespace with the paths of temporary
+//!             files appended as arguments, one per file in stacking order with the
+//!             extension of its name, and writing its standard output as the
+//!             stacked file, as in --stack-cmd '*.pb=protostack --merge'. Splits at
+//!             the first = unlike other options, so the command may contain = as in
+//!             --stack-cmd '*.pb=protostack --level=3' whereas the glob may not.
+//!             Takes precedence over stacking supported formats natively. Fails if
+//!             the command exits unsuccessfully.
+//!
+//!         --stack-order <order>
+//!             Sorts stacked files.
+//!
+//!             Stacks identically named files sorted by the paths of their input
+//!             ZIP archives, by them in natural order comparing runs of digits by
+//!             their numeric values as in part-2.npy before part-10.npy, or by
+//!             their last modification times instead of in the given order. Ties
+//!             keep the given order.
+//!
+//!             [possible values: name, natural, mtime]
+//!
+//!         --stack-by-index <name>
+//!             Sorts stacked files by index arrays.
+//!
+//!             Stacks identically named files sorted by the integer of the NPY file
+//!             of the given name in their input ZIP archives, as in
+//!             --stack-by-index order.npy for shards carrying their own position.
+//!             The NPY file must hold a scalar or a single integer. Ties keep the
+//!             given order. Fails if an input ZIP archive of stacked files lacks
+//!             the NPY file.
+//!
+//!         --on-missing-stack-member <policy>
+//!             Handles arrays missing in some input ZIP archives.
+//!
+//!             Fails, merges instead of stacking, or stacks zero-filled arrays for
+//!             NPY files to be stacked but missing in some of the input ZIP
+//!             archives containing other NPY files to be stacked, preventing the
+//!             silently smaller stacks of stacking the present ones only by
+//!             default. Zero-filled arrays take the shape and data-type of the
+//!             first present one and the places of the missing ones in the given
+//!             order, so --stack-order and --stack-by-index are not supported.
+//!
+//!             [possible values: error, skip, zero]
+//!
+//!         --max-stack-inputs <files>
+//!             Fails on more identically named files to be stacked than given.
+//!
+//!             Counts the files to be stacked by name after indexing all input ZIP
+//!             archives before reading any of them, guarding against globs
+//!             accidentally matching too many input ZIP archives. Stacks of any
+//!             size are allowed by default.
+//!
+//!         --csv-header=<bool>
+//!             Treats first rows of CSV files as headers.
+//!
+//!             Requires the headers of stacked CSV files to be identical and writes
+//!             them once followed by the records of each file in the given order.
+//!             Otherwise, all rows are records.
+//!
+//!             [default: true]
+//!
+//!         --json-merge <array|object>
+//!             Merges stacked JSON files by top level.
+//!
+//!             Requires the top levels of stacked JSON files to be arrays and
+//!             concatenates their elements with array or to be objects and merges
+//!             their keys shallowly with object, with values of later files
+//!             superseding those of identical keys in earlier ones, keeping the
+//!             position of the key first seen.
+//!
+//!             [default: array]
+//!             [possible values: array, object]
+//!
+//!         --zstd-long[=<window_log>...]
+//!             Enables zstd long-distance matching.
+//!
+//!             Recompresses zstd files with long-distance matching within a window
+//!             of 2^window_log bytes, 2^27 bytes (128 MiB) by default, improving
+//!             the ratio of large files containing repetitions far apart.
+//!             Decompressing needs as much memory for the window. Window logs range
+//!             from 10 to 27, as standard zstd decoders reject larger windows by
+//!             default. Files are compressed into a temporary file first and then
+//!             copied, so they are neither aligned nor have extra fields. Checks
+//!             window logs instead of levels with no output ZIP archive.
+//!
+//!         --zstd-threads <count>
+//!             Compresses zstd files with worker threads.
+//!
+//!             Recompresses zstd files with the given count of worker threads
+//!             compressing parts of a file in parallel, speeding up large files as
+//!             files themselves are written one after another. Zero workers
+//!             compress on the calling thread. Otherwise, the compressed data may
+//!             differ from the one of zero workers. Files are compressed into a
+//!             temporary file first and then copied like with --zstd-long.
+//!
+//!             [default: 0]
+//!
+//!         --max-level <level>
+//!             Caps compression levels.
+//!
+//!             Lowers the levels given by --recompress exceeding the given level,
+//!             bounding the time spent compressing without editing every method.
+//!             The cap is clamped into the range of levels of each algorithm, so a
+//!             cap of 0 lowers bzip2 and zstd levels to 1. Default levels of
+//!             methods without a level are kept. Clamping is logged at debug level.
+//!
+//!         --cache-dir <path>
+//!             Caches recompressed files in directory.
+//!
+//!             Reuses the compressed data of files recompressed before with the
+//!             same method and level instead of recompressing them, keyed by the
+//!             BLAKE3 hash of their uncompressed data, method, and level. Stacked,
+//!             precompressed, aligned, and zstd files of --zstd-long or
+//!             --zstd-threads are not cached, neither are files with extra fields.
+//!             Costs reading each cached file once more. Entries are never
+//!             invalidated but evicted, so delete the directory after upgrading
+//!             rezip to recompress with possibly changed compressors.
+//!
+//!         --cache-size <bytes>
+//!             Limits cache size.
+//!
+//!             Evicts the least recently used entries of --cache-dir after writing
+//!             the output ZIP archive until their total size does not exceed the
+//!             given bytes.
+//!
+//!             [default: 1GiB]
+//!
+//!         --no-cache
+//!             Disables --cache-dir
+//!
+//!     -v, --verbose
+//!             Logs status information to stderr.
+//!
+//!             The more occurrences, the more verbose, with three at most, logging
+//!             at info, debug, or trace level. Overrides the level of rezip
+//!             otherwise filtered by the RUST_LOG environment variable as in
+//!             RUST_LOG=rezip=debug, defaulting to warn level. Reports the
+//!             wall-clock time spent indexing and compressing per input ZIP archive
+//!             and in total along with stacking and the throughput at debug level.
+//!             Keeps stdout free for data like the listing of --list.
+//!
+//!     -q, --quiet
+//!             Prints errors only.
+//!
+//!             Suppresses status information and warnings, overriding --verbose.
+//!
+//!         --log-format <format>
+//!             Logs status information formatted.
+//!
+//!             Logs text timestamped and leveled or prints JSON objects, one per
+//!             line with an "event" field.
+//!
+//!             [default: text]
+//!             [possible values: text, json]
+//!
+//!     -h, --help
+//!             Print help information
+//!
+//!     -V, --version
+//!             Print version information
+//!
+//! Exits with 0 on success, 1 on errors, 2 on checks failing as not requested, and
+//! 3 on usage errors.
+//! ```
+//!
+//! # Library
+//!
+//! The command-line interface is available as [`Rezip`], which is parsed from arguments and run
+//! with [`Rezip::run()`] logging status information or with [`Rezip::run_with_progress()`]
+//! passing each [`RezipEvent`] to a callback instead, e.g., to drive a custom user interface.
+//!
+//! ```no_run
+//! use clap::Parser;
+//! use rezip::{Rezip, RezipEvent};
+//!
+//! let rezip = Rezip::parse_from(["rezip", "a.zip", "b.zip", "-o", "c.zip"]);
+//! rezip.run_with_progress(|event| {
+//!     if let RezipEvent::Written { name, bytes } = event {
+//!         eprintln!("{}: {} bytes", name.display(), bytes);
+//!     }
+//! })?;
+//! # Ok::<(), color_eyre::Report>(())
+//! ```
+
+#![forbid(unsafe_code)]
+#![forbid(missing_docs)]
+#![allow(clippy::collapsible_else_if)]
+#![allow(clippy::redundant_else)]
+#![allow(clippy::map_unwrap_or)]
+#![allow(clippy::large_enum_variant)]
+
+use clap::{crate_authors, crate_version, AppSettings, ArgGroup, Parser, ValueEnum};
+use color_eyre::{eyre::eyre, eyre::WrapErr, Report, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use glob::{glob as glob_expand, MatchOptions, Pattern};
+use indexmap::IndexMap;
+use log::{debug, log, log_enabled, warn, Level, LevelFilter};
+use ndarray::{ArrayD, Axis, IxDyn};
+#[cfg(feature = "half")]
+use ndarray_npy::ReadDataError;
+use ndarray_npy::{ReadNpyError, ReadNpyExt, ReadableElement, WritableElement, WriteNpyExt};
+use regex::Regex;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, Metadata, OpenOptions};
+use std::io::{self, copy, BufReader, BufWriter, Read, Seek, Write};
+use std::mem;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+use tempfile::{NamedTempFile, TempPath};
+use time::{format_description::well_known::Iso8601, OffsetDateTime, UtcOffset};
+use walkdir::WalkDir;
+use xz2::write::XzEncoder;
+use zip::{
+	read::ZipFile,
+	result::{ZipError, ZipResult},
+	write::FileOptions,
+};
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+const DEFAULT_RECOMPRESS: &[&str] = &["stored"];
+const DEFAULT_ALIGN: &[&str] = &["64", "*.so=4096"];
+const DEFAULT_STACK: &[&str] = &["0"];
+
+/// Merges ZIP/NPZ archives recompressed or aligned and stacks NPY arrays
+///
+/// Options accepting <[glob=]value> pairs use the given values for matching file names in input ZIP
+/// archives. Matches of former pairs are superseded by matches of latter pairs. Omitting [glob=]
+/// by only passing a value assumes the * glob pattern matching all file names whereas an empty glob
+/// pattern matches no file names. An empty value disables the option for the file names matching
+/// the glob pattern. Passing a single pair with an empty glob pattern and an empty value, that is a
+/// = only, disables an option with default values entirely as in --recompress = whereas passing no
+/// pairs as in --recompress keeps assuming the default values.
+#[derive(Parser, Debug, Clone)]
+#[clap(
+	version = crate_version!(),
+	author = crate_authors!(),
+	global_setting = AppSettings::DeriveDisplayOrder,
+	arg_required_else_help = true,
+	after_help = "Exits with 0 on success, 1 on errors, 2 on checks failing as not requested, and 3 \
+		on usage errors.",
+	group(ArgGroup::new("sink").args(&["output", "extract", "filter"])),
+)]
+pub struct Rezip {
+	/// Merges or checks input ZIP archives.
+	///
+	/// Stacks identically named files in different input ZIP archives in the order given by parsing
+	/// supported file formats like NPY (NumPy array file). Otherwise, only the file in the last
+	/// given input ZIP archive is merged into the output ZIP archive.
+	///
+	/// Input TAR archives with .tar, .tar.gz, .tgz, .tar.zst, or .tzst extensions are read like
+	/// input ZIP archives of uncompressed files with their names, sizes, modification times, and
+	/// Unix modes. Gzipped or Zstandard-compressed ones are decompressed into a temporary file
+	/// first, so their files are read in any order as stacking requires. Other than regular files
+	/// and directories are skipped.
+	///
+	/// Reads further globs from list files given as @path, one glob per line with leading and
+	/// trailing whitespace trimmed, ignoring blank lines and lines starting with #. Globs in list
+	/// files are taken verbatim, so paths with spaces need no quoting, whereas glob metacharacters
+	/// are matched literally in brackets as in [*]. Relative globs are relative to the working
+	/// directory, not to the list file.
+	#[clap(value_name = "glob")]
+	inputs: Vec<String>,
+	/// Sorts input ZIP archives.
+	///
+	/// Sorts the paths of input ZIP archives matched by all globs as given, by name, in natural
+	/// order comparing runs of digits by their numeric values, or by their last modification times
+	/// before indexing them. Sets the order of stacking and the priority of merging, whereas
+	/// --stack-order re-sorts identically named files to be stacked afterwards. Ties keep the
+	/// given order.
+	#[clap(long, value_name = "order", value_enum, default_value = "asis")]
+	input_order: InputOrder,
+	/// Writes output ZIP archive.
+	///
+	/// With no output ZIP archive, checks if files in input ZIP archives are as requested according
+	/// to --recompress and --align. Recompress levels are checked exactly for bzip2 and on a best
+	/// effort basis for zstd by inferring them from the window size, which only tells levels with
+	/// different default window sizes apart and nothing if the window size is the content size. The
+	/// levels of deflated files are not recoverable and --merge matches are not checked.
+	///
+	/// Writes a temporary file next to the output ZIP archive named like it with a .tmp-
+	/// extension and a random suffix, which is renamed to the output ZIP archive when finished and
+	/// removed on failure. Volumes of --split-size are written alike.
+	///
+	/// Writes one output ZIP archive per input ZIP archive if the path contains {stem}, as in
+	/// --output 'out/{stem}.zip', replacing it with the file stem of the input ZIP archive. Runs
+	/// once per input ZIP archive, so files are neither stacked nor merged across input ZIP
+	/// archives. Fails if two input ZIP archives share a file stem.
+	#[clap(short, long, value_name = "path")]
+	output: Option<PathBuf>,
+	/// Writes temporary output ZIP archive into directory.
+	///
+	/// Writes the temporary file of --output into the given directory instead of next to the
+	/// output ZIP archive, failing early unless both are on the same file system for renaming it
+	/// atomically.
+	#[clap(long, value_name = "path", requires = "output")]
+	tmpdir: Option<PathBuf>,
+	/// Writes output ZIP archive as self-extracting shell script.
+	///
+	/// Prepends a POSIX shell script extracting the output ZIP archive into a new temporary
+	/// directory with unzip and printing its path, and makes it executable by its owner. Offsets
+	/// and --align paddings account for the script, whereas ZIP readers skip it as leading data.
+	/// Requires sh, mktemp, and unzip on the extracting system. Tools rewriting ZIP archives may
+	/// drop the script.
+	#[clap(long, requires = "output", conflicts_with = "split-size")]
+	sfx: bool,
+	/// Extracts files into directory.
+	///
+	/// Merges and stacks files as --output does into a temporary ZIP archive next to the given
+	/// directory and extracts it into the directory instead, creating missing directories and
+	/// restoring Unix modes and last modification times of files where available. Fails on
+	/// existing files unless --force is given. Of the options requiring --output, only
+	/// --collision, --strict, --dedup, and --no-dir-entries apply.
+	#[clap(short = 'x', long, value_name = "dir")]
+	extract: Option<PathBuf>,
+	/// Filters ZIP archive from stdin to stdout.
+	///
+	/// Reads a single input ZIP archive from stdin instead of globs and writes the output ZIP
+	/// archive to stdout as --output does, as in rezip --filter -r zstd < in.zip > out.zip. As
+	/// ZIP archives are read from their central directory at the end and written by seeking back
+	/// to complete local file headers, both are buffered in temporary files, so memory usage stays
+	/// low while disk usage doubles. Nothing is written to stdout on failure. Of the options
+	/// requiring --output, none apply.
+	#[clap(long, conflicts_with_all = &["inputs", "input-order"])]
+	filter: bool,
+	/// Lists files in input ZIP archives.
+	///
+	/// Prints a table per input ZIP archive with the size, method, compressed size, compression
+	/// ratio, last modification time, CRC-32, and alignment of each file without extracting it.
+	/// The alignment is the remainder of dividing the start of the file data by the bytes requested
+	/// by --align, being 0 when aligned. Files in input directories and TAR archives have no CRC-32
+	/// to list before reading them.
+	#[clap(short, long, conflicts_with = "sink")]
+	list: bool,
+	/// Lists files in input ZIP archives as JSON.
+	///
+	/// Prints one JSON array of objects with the name, method, compressed size, uncompressed size,
+	/// CRC-32 in hexadecimal, ISO 8601 last modification time, directory flag, Unix mode, data
+	/// start, and alignment of each file as listed by --list, along with the input ZIP archive it
+	/// is from. Missing values are null. Sizes and offsets beyond 2^53 are strings as JSON numbers
+	/// cannot represent them exactly in common parsers.
+	#[clap(long, conflicts_with_all = &["sink", "list"])]
+	list_json: bool,
+	/// Verifies files by decompressing them.
+	///
+	/// Reads each file of the input ZIP archives in full while checking them, verifying their
+	/// CRC-32 if any. Reports each file failing to decompress and fails after checking all files
+	/// if any did.
+	#[clap(long, conflicts_with_all = &["sink", "list", "list-json"])]
+	verify: bool,
+	/// Reports compression methods of files in input ZIP archives.
+	///
+	/// Prints a histogram of the compression methods of all files without checking them against
+	/// --recompress, with one event per file at -v verbosity. Levels are inferred as far as
+	/// possible when checking, exactly for bzip2 and as a range for zstd as in zstd:3-8, whereas
+	/// deflated levels are not recoverable. Directories are skipped.
+	#[clap(long, conflicts_with_all = &["sink", "list", "list-json", "verify"])]
+	report_methods: bool,
+	/// Writes existing output ZIP archive.
+	#[clap(short, long)]
+	force: bool,
+	/// Updates existing output ZIP archive.
+	///
+	/// Reads the files of an existing output ZIP archive as if it were given as first input ZIP
+	/// archive, except that identically named files in input ZIP archives supersede them instead of
+	/// being stacked with them and that --merge, --rename, --normalize-paths, and --prefix are not
+	/// applied to them. Replaces the existing output ZIP archive when finished and rewrites the
+	/// file of --checksums.
+	#[clap(long, requires = "output", conflicts_with = "split-size")]
+	update: bool,
+	/// Recompresses or aligns a single input ZIP archive only.
+	///
+	/// Writes the files of exactly one input ZIP archive in their order as requested by
+	/// --recompress and --align, neither stacking nor merging them nor adding missing directories.
+	/// Fails if the globs match more than one input ZIP archive, whereas --output with {stem}
+	/// repacks each of them on its own.
+	#[clap(
+		long,
+		requires = "output",
+		conflicts_with_all = &["update", "merge", "glob-as-group", "stack", "stack-cmd"]
+	)]
+	repack_only: bool,
+	/// Comments output ZIP archive by file.
+	///
+	/// Reads the comment of the output ZIP archive verbatim from the given file, failing if it
+	/// exceeds the limit of 65535 bytes. Each volume of --split-size is commented alike.
+	#[clap(long, value_name = "path", requires = "output")]
+	comment_file: Option<PathBuf>,
+	/// Appends data of file to output ZIP archive.
+	///
+	/// Writes the bytes of the given file verbatim after the end of central directory record when
+	/// finished, as for formats placing metadata behind the ZIP archive. ZIP readers ignore
+	/// trailing data as long as they find the record by searching backwards, so fails if the data
+	/// and --comment-file exceed 65535 bytes together, keeping the output ZIP archive valid. With
+	/// --split-size, the data is appended to the last volume only. Tools rewriting ZIP archives
+	/// may drop the data.
+	#[clap(long, value_name = "path", requires = "output")]
+	append_trailer: Option<PathBuf>,
+	/// Sets modification time of output ZIP archive.
+	///
+	/// Sets the last modification time of the output ZIP archive itself when finished to the
+	/// given ISO 8601 time as in 2000-01-01T00:00:00Z, to the time of this run with now, or with
+	/// source to the newest last modification time of its files, leaving it untouched if there
+	/// are none. Unlike --entry-timestamp-source, this lets build systems comparing modification
+	/// times consider the output ZIP archive as old as its contents. With --split-size, only the
+	/// last volume is touched.
+	#[clap(
+		long,
+		value_name = "iso8601|now|source",
+		requires = "output",
+		parse(try_from_str = parse_touch_output)
+	)]
+	touch_output: Option<TouchOutput>,
+	/// Verifies output ZIP archive before renaming it.
+	///
+	/// Reopens the temporary output ZIP archive when finished and reads each file in full,
+	/// verifying its CRC-32, and the shape of each stacked array. Fails instead of renaming it to
+	/// the output ZIP archive if any are wrong. Each volume of --split-size is verified alike.
+	#[clap(long, requires = "output")]
+	verify_output: bool,
+	/// Recomputes CRC-32 of files copied as is.
+	///
+	/// Decompresses files whose compressed data is copied without recompressing it and fails if
+	/// their CRC-32 mismatches the one stored in the input ZIP archive, detecting its corruption.
+	/// Otherwise, copied files are trusted without reading them twice, which is about as slow as
+	/// reading them once more when compressed and barely slower when stored.
+	#[clap(long, requires = "output")]
+	recompute_crc: bool,
+	/// Writes sorted list of files into output ZIP archive.
+	///
+	/// Adds a stored file of the given name last, listing the names of all other files and
+	/// directories written to the output ZIP archive sorted and separated by LF terminators. With
+	/// --split-size, it is added to the last volume listing the files of all volumes. Fails if the
+	/// name is taken by another file.
+	#[clap(long, value_name = "name", requires = "output")]
+	index_entry: Option<PathBuf>,
+	/// Skips unreadable input ZIP archives and files.
+	///
+	/// Prints a warning for each input ZIP archive or file which cannot be read and excludes it
+	/// instead of aborting. Still fails after merging or checking the remaining ones if any were
+	/// skipped.
+	#[clap(long)]
+	continue_on_error: bool,
+	/// Stops at the first output ZIP archive failing to be written.
+	///
+	/// Aborts the remaining runs of --output with {stem} as soon as one fails. This is the
+	/// default and overrides --no-fail-fast.
+	#[clap(long, overrides_with = "no-fail-fast")]
+	fail_fast: bool,
+	/// Writes all output ZIP archives before failing.
+	///
+	/// Continues with the remaining runs of --output with {stem} if one fails and reports the
+	/// errors of all failed output ZIP archives together when finished, failing if any did. A
+	/// single output ZIP archive is never written partially, so this has no effect otherwise.
+	#[clap(long, overrides_with = "fail-fast")]
+	no_fail_fast: bool,
+	/// Keeps last of identically named files in an input ZIP archive.
+	///
+	/// Prints a warning for each file in an input ZIP archive named like a former file in the
+	/// same input ZIP archive and keeps the latter instead of aborting.
+	#[clap(long)]
+	allow_duplicate_inputs: bool,
+	/// Selects among identically named files not stacked.
+	///
+	/// Merges the file of the last or first given input ZIP archive, the newest one by last
+	/// modification time, or the largest one by uncompressed size. Ties are resolved in favor of
+	/// the last given input ZIP archive. Stacked files are unaffected. Defaults to the last one.
+	#[clap(long, value_name = "policy", value_enum, requires = "sink")]
+	collision: Option<CollisionPolicy>,
+	/// Orders files as in their input ZIP archives.
+	///
+	/// Writes the files ordered by the input ZIP archive and the position in it of the file
+	/// selected by --collision instead of by the first input ZIP archive they are seen in, which
+	/// interleaves the files of different input ZIP archives. Keeps the order of a single input
+	/// ZIP archive exactly. Stacked files are positioned like merged ones, since they are stacked
+	/// from several input ZIP archives. Directories added for nested files precede their first
+	/// file.
+	#[clap(long, requires = "sink")]
+	preserve_order: bool,
+	/// Keeps identically named files not stacked by numbering their names.
+	///
+	/// Writes the file of each input ZIP archive instead of selecting one by --collision, numbering
+	/// all but the first in the given order of input ZIP archives as in data.npy, data.1.npy, and
+	/// data.2.npy. Numbers are inserted before the first extension and skip names of other files.
+	/// Directories are merged as usual. Globs of other options match the numbered names.
+	#[clap(long, requires = "sink", conflicts_with = "collision")]
+	rename_collisions: bool,
+	/// Fails on identically named files not stacked.
+	///
+	/// Aborts instead of merging only one of identically named files of different input ZIP
+	/// archives which are neither stacked nor selected by an explicitly given --collision policy,
+	/// preventing the accidental loss of shards.
+	#[clap(long, requires = "sink")]
+	strict: bool,
+	/// Limits simultaneously open input ZIP archives.
+	///
+	/// Closes the least recently used input ZIP archive when opening another one would exceed the
+	/// given count of at least one. Input ZIP archives are opened once for indexing their files and
+	/// reopened on demand while merging, which traverses input directories again and decompresses
+	/// gzipped input TAR archives again. Files of input directories are opened one at a time.
+	#[clap(long, value_name = "count", default_value = "64")]
+	max_open_inputs: usize,
+	/// Follows symbolic links in input directories.
+	///
+	/// Traverses symbolically linked directories and reads symbolically linked files of input
+	/// directories instead of skipping them. Input directories given as symbolic links are always
+	/// followed. Unlike prior versions, symbolic links are skipped by default.
+	#[clap(long)]
+	follow_links: bool,
+	/// Names files of input directories relative to base directory.
+	///
+	/// Strips the given directory from the paths of files in input directories, as in -C mydir
+	/// mydir storing mydir/file as file instead, whereas the base directory itself is omitted.
+	/// Fails if a file of an input directory is not under the base directory.
+	#[clap(short = 'C', long, value_name = "dir")]
+	base: Option<PathBuf>,
+	/// Splits output ZIP archive into volumes.
+	///
+	/// Starts a new volume whenever writing the next file would exceed the given bytes. Finished
+	/// volumes are renamed to .z01, .z02, and so on whereas the last volume keeps the output path.
+	/// Each volume is a self-contained ZIP archive as files are never split across volumes. A file
+	/// exceeding the given bytes on its own is written into its own volume. The size of a file to
+	/// write is estimated by its size in the input ZIP archive, whereas the size of a stacked array
+	/// is estimated by the total size of the arrays it stacks as it is written as a single file.
+	#[clap(long, value_name = "bytes", requires = "output")]
+	split_size: Option<u64>,
+	/// Uses ZIP64 extensions.
+	///
+	/// Always writes ZIP64 extended information of files, enabling files and output ZIP archives
+	/// of more than 4 GiB or 65535 files, or never writes ZIP64 extensions for tools not supporting
+	/// them, failing on the first file exceeding these limits.
+	#[clap(
+		long,
+		value_name = "mode",
+		value_enum,
+		default_value = "always",
+		requires = "output"
+	)]
+	zip64: Zip64Mode,
+	/// Writes no ZIP64 extensions.
+	///
+	/// Is an alias of --zip64 never.
+	#[clap(long, requires = "output", conflicts_with = "zip64")]
+	no_zip64: bool,
+	/// Writes checksums of files in output ZIP archive.
+	///
+	/// Writes the digests of the uncompressed files in the order they are written to the output ZIP
+	/// archive into a sidecar file named after the output ZIP archive with the algorithm as
+	/// additional extension, each line listing a digest and a name separated by two spaces as
+	/// expected by sha256sum and b3sum. Stacked arrays are digested as written, files of
+	/// --cache-dir while hashing their key. Files copied without recompression are read once more
+	/// to digest their uncompressed data.
+	#[clap(long, value_name = "algorithm", value_enum, requires = "output")]
+	checksums: Option<ChecksumAlgorithm>,
+	/// Reports changes of files compared to previous ZIP archive.
+	///
+	/// Compares the uncompressed files of the output ZIP archive when finished with identically
+	/// named ones in the given ZIP archive by their SHA-256 digests, reporting each file as added,
+	/// removed, modified, or unchanged with -v verbosity and the counts of each in total. The
+	/// previous ZIP archive is read before writing, so it may be the output ZIP archive itself
+	/// when overwritten with --force. The output ZIP archive is not altered.
+	#[clap(
+		long,
+		value_name = "path",
+		requires = "output",
+		conflicts_with = "split-size"
+	)]
+	diff_against: Option<PathBuf>,
+	/// Detects files of identical content.
+	///
+	/// Digests the uncompressed data of each file to write with SHA-256 and either drops files
+	/// identical to a formerly written one or only reports them and the bytes dropping them would
+	/// save. Stacked arrays and directories are never deduplicated. Costs reading each file once
+	/// more and keeping one digest and name per written file in memory.
+	#[clap(long, value_name = "mode", value_enum, requires = "sink")]
+	dedup: Option<DedupMode>,
+	/// Names files by their content.
+	///
+	/// Renames each file to the hexadecimal BLAKE3 hash of its uncompressed data followed by the
+	/// extension of its name, dropping files of identical content and directories. Stacked files
+	/// are hashed after stacking. Globs of other options still match the original names. Costs
+	/// reading each file once more and writing stacked files to temporary files first.
+	#[clap(long, requires = "sink")]
+	content_address: bool,
+	/// Matches globs of options case-insensitively.
+	///
+	/// Matches the globs of --merge, --prefix, --recompress, --align, and --stack regardless of
+	/// case as in --align '*.so=4096' matching lib.SO. Globs of input ZIP archives are unaffected.
+	#[clap(long)]
+	ignore_case: bool,
+	/// Matches globs without slashes against file names only.
+	///
+	/// Matches the globs of --merge, --prefix, --recompress, --align, and --stack not containing a
+	/// slash against the last component of paths as in --align 'foo.so=4096' matching lib/foo.so
+	/// instead of requiring '*/foo.so'. Globs containing a slash still match whole paths.
+	#[clap(long)]
+	match_basename: bool,
+	/// Reads <[glob=]value> pairs from TOML file.
+	///
+	/// Reads arrays of pairs as passed on the command line from the recompress, align, stack, and
+	/// merge keys of the given TOML file, as in recompress = ['*.npy=zstd:3', '*.so=stored'].
+	/// Pairs of the command line are appended to the ones of the file, so their matches supersede
+	/// the file's, whereas options left at their default values take the pairs of the file only.
+	/// Fails on other keys.
+	#[clap(long, value_name = "path")]
+	rules: Option<PathBuf>,
+	/// Merges files as if they were in ZIP archives.
+	///
+	/// Merges files as if they were in different ZIP archives and renames them to the given names.
+	/// With empty names, keeps original names, effectively creating a ZIP archive from input files.
+	/// Files in input ZIP archives matching the globs are renamed alike. In names, {} is replaced
+	/// by the file name of the matching file, which is appended to names ending with a slash as in
+	/// --merge 'old/*.bin=new/'.
+	///
+	/// Files keep their permissions and last modification times unless overridden by --merge-mode
+	/// and --merge-time.
+	#[clap(short, long, value_name = "[glob=]name")]
+	merge: Vec<String>,
+	/// Merges files matched by each input glob as one group.
+	///
+	/// Merges the regular files matched by an input glob as if --merge renamed them to a name
+	/// derived from the glob, so they are stacked together as in --glob-as-group 'part-*.npy'
+	/// stacking part-0.npy, part-1.npy, and so on as part.npy. The name is the file name of the
+	/// glob up to its first metacharacter trimmed of trailing -, _, and . characters, followed by
+	/// its extension after the last metacharacter. Fails if the name is empty, as for '*.npy'.
+	/// Matched input ZIP archives are merged as files too, whereas matches of --merge take
+	/// precedence.
+	#[clap(long)]
+	glob_as_group: bool,
+	/// Sets permissions of files of input directories.
+	///
+	/// Overrides the Unix permissions of files and directories of input directories and of files
+	/// of --merge matching the globs with the given octal mode as in --merge-mode 'bin/*=0755'.
+	/// Globs match the names before --merge renames.
+	#[clap(long, value_name = "[glob=]mode")]
+	merge_mode: Vec<String>,
+	/// Sets modification time of files of input directories.
+	///
+	/// Overrides the last modification times of files and directories of input directories and of
+	/// files of --merge matching the globs with the given Unix timestamp in seconds as in
+	/// --merge-time 315532800 for reproducible output ZIP archives. Globs match the names before
+	/// --merge renames.
+	#[clap(long, value_name = "[glob=]seconds")]
+	merge_time: Vec<String>,
+	/// Sets modification times of output files.
+	///
+	/// Writes files with the last modification time of their source file, the time of this run
+	/// with now, the given ISO 8601 time as in fixed=1980-01-01T00:00:00Z, or with max the newest
+	/// time of the files stacked into them. Stacked files take the time of the last file stacked
+	/// into them with source, whereas fixed or max make them independent of the stack order.
+	/// Directories are not affected.
+	#[clap(
+		long,
+		value_name = "source|now|fixed=<iso8601>|max",
+		default_value = "source",
+		parse(try_from_str = parse_timestamp_source)
+	)]
+	entry_timestamp_source: TimestampSource,
+	/// Renames files by regular expression.
+	///
+	/// Substitutes all matches of the regular expression in the names of files by the replacement,
+	/// which may refer to capture groups as in $1 or ${name}. Splits at the first = not escaped as
+	/// \= in the regular expression, so the replacement may contain = as in --rename
+	/// 'a\.txt=k=v.txt'. Renames are applied in the given order after --merge renames. Renamed
+	/// files colliding with others are merged or stacked alike. Renames resulting in empty or
+	/// absolute names are invalid.
+	#[clap(long, value_name = "regex=replacement")]
+	rename: Vec<String>,
+	/// Normalizes names of files.
+	///
+	/// Replaces backslashes with slashes, removes empty and . components, and ends names of
+	/// directories with a slash before --merge globs and --rename expressions are applied. Names
+	/// becoming identical are merged or stacked like identically named files.
+	#[clap(long)]
+	normalize_paths: bool,
+	/// Decodes names of files as UTF-8.
+	///
+	/// Takes the names of files in input ZIP archives as UTF-8 whenever they are valid UTF-8, even
+	/// if their language encoding flag (bit 11) is unset, which many archivers omit. Otherwise,
+	/// such names are decoded as CP437 per specification, mangling UTF-8 ones. Warns about names
+	/// being neither and keeps them decoded as CP437. Output ZIP archives always store names as
+	/// UTF-8 with the flag set for non-ASCII ones, whereas ASCII names read the same either way.
+	#[clap(long)]
+	utf8_names: bool,
+	/// Strips leading components of names of files.
+	///
+	/// Removes the given number of leading directories from the names of files in input ZIP
+	/// archives like tar does, as in --strip-components 1 naming pkg/lib/a.so as lib/a.so.
+	/// Skips files and directories with no more components than stripped, like pkg/ and
+	/// README, instead of keeping them. Applies after --normalize-paths and before --merge
+	/// globs and --rename expressions.
+	#[clap(long, value_name = "count")]
+	strip_components: Option<usize>,
+	/// Prepends path to names of files.
+	///
+	/// Nests the files of input ZIP archives matching the globs under the given path as in
+	/// --prefix 'a.zip=vendor/a' --prefix 'b.zip=vendor/b', preventing collisions of merged files.
+	/// The path is prepended after --rename expressions are applied.
+	#[clap(long, value_name = "[glob=]path")]
+	prefix: Vec<String>,
+	/// Omits directories in output ZIP archive.
+	///
+	/// Writes files only, as their names imply the directories containing them. Empty directories
+	/// are lost, including the ones of input directories. Otherwise, directories missing in input
+	/// ZIP archives are added for every file and directory nested in them, including the ones of
+	/// --prefix.
+	#[clap(long, requires = "sink")]
+	no_dir_entries: bool,
+	/// Collapses variants of names of directories.
+	///
+	/// Merges directories whose names differ in case or separators only, like lib/, Lib/, and
+	/// lib\ of different input archives, into one named as the first one seen. Files keep their
+	/// names.
+	#[clap(long, conflicts_with = "no-dir-entries")]
+	dedup_dirs: bool,
+	/// Excludes files smaller than the given size.
+	///
+	/// Skips files of input ZIP archives while indexing them by their uncompressed sizes in bytes
+	/// or with a binary suffix as in 4KiB, 16MiB, 1GiB, or 2TiB. Identically named files are
+	/// excluded one by one, leaving fewer or no files to be stacked. Directories are unaffected.
+	#[clap(long, value_name = "size", parse(try_from_str = parse_size))]
+	min_size: Option<u64>,
+	/// Excludes files larger than the given size.
+	///
+	/// Skips files like --min-size does, as in --max-size 100MiB.
+	#[clap(long, value_name = "size", parse(try_from_str = parse_size))]
+	max_size: Option<u64>,
+	/// Fails on names of files longer than the given bytes.
+	///
+	/// Checks the names of files in input ZIP archives while indexing them before they are
+	/// renamed, guarding against pathological input ZIP archives.
+	#[clap(long, value_name = "bytes")]
+	max_name_length: Option<usize>,
+	/// Fails on names of files nested deeper than the given depth.
+	///
+	/// Checks the names of files like --max-name-length does by counting their components, as in
+	/// 3 for a/b/c.txt and a/b/c/. Fails on absolute names and names with .. components
+	/// regardless of their depth.
+	#[clap(long, value_name = "depth")]
+	max_depth: Option<usize>,
+	/// Fails on more files in output ZIP archive than given.
+	///
+	/// Counts the files and directories to be written after indexing all input ZIP archives
+	/// before reading any of them, including the directories added for nested files.
+	#[clap(long, value_name = "files")]
+	max_entries: Option<usize>,
+	/// Writes files recompressed.
+	///
+	/// Supported methods are stored (uncompressed), deflated[:0-9] (most common) with 6 as default
+	/// level, bzip2[:1-9] (high ratio) with 6 as default level, and zstd[:1-21] (modern) with 3 as
+	/// default level. Files already compressed with the requested method are copied without
+	/// recompression unless a level is given, they are stacked, --entry-timestamp-source changes
+	/// their modification times, or --zip64 always writes ZIP64 extended information of files as
+	/// by default.
+	///
+	/// Levels of bzip2 select block sizes of 100 to 900 kB. A work factor of 1 to 250 may follow as
+	/// in bzip2:9,100, 30 by default, bounding the effort spent sorting repetitive data before
+	/// falling back to a slower but steady algorithm. It affects the speed only, not the compressed
+	/// data. Files are compressed into a temporary file first and then copied like with --zstd-long.
+	///
+	/// The keep method keeps the original methods of matching files as in --recompress zstd
+	/// --recompress '*.jpg=keep', like an empty method as in '*.jpg='. Files matching no globs keep
+	/// their original methods as well, whereas without this option all files are stored. An empty
+	/// glob and method as in --recompress = matches no files, keeping the original methods of all.
+	///
+	/// The auto method selects stored for files already compressed by extension like .jpg, .png,
+	/// .zip, or .gz, for empty files, and for files of more than 7.5 bits of entropy per byte in
+	/// their first 64 KiB, and zstd otherwise. Methods of other matching globs take precedence
+	/// over auto regardless of their order.
+	///
+	/// Globs qualified by globs of input ZIP archives as in --recompress 'vendor.zip:*.so=stored'
+	/// match files of matching input ZIP archives only, as they do for --precompress and --align.
+	/// Stacked files match no qualified globs. Qualifiers end at the first colon preceded by an
+	/// archive extension like .zip, .npz, .tar, or .tgz, so other colons match literally as in
+	/// 'e/b:c.txt', whereas [:] matches a literal colon after such an extension as in
+	/// 'a.zip[:]b.txt'.
+	#[clap(short, long, value_name = "[glob=]method", default_values = DEFAULT_RECOMPRESS)]
+	recompress: Vec<String>,
+	/// Writes files precompressed and stored.
+	///
+	/// Compresses the data of files with the given codec and writes the compressed stream stored,
+	/// so it is aligned like other stored files but, unlike with --recompress, not decompressed by
+	/// ZIP readers. The only supported codec is xz[:0-9] with 6 as default level. Names are kept,
+	/// so consumers must know which files are wrapped, like by the xz magic number. Checksums
+	/// digest the compressed stream as written.
+	#[clap(long, value_name = "[glob=]codec", requires = "output")]
+	precompress: Vec<String>,
+	/// Writes compressed files precompressed, stored, and aligned.
+	///
+	/// Compresses the data of files to be compressed and aligned by --recompress and --align with
+	/// the given codec instead and writes the compressed stream stored and aligned, trading
+	/// compatibility for both alignment and size reduction. The only supported codec is
+	/// zstd[:1-21] with 3 as default level. The stream ends with a 16-byte trailer of the
+	/// decompressed length as little-endian 64-bit integer, the codec name zstd, and the magic
+	/// RZIP. ZIP readers extract the stream as is, so a companion reader checks whether a file
+	/// ends with RZIP, takes the codec name from the 4 bytes before, decompresses the stream
+	/// without its trailer, and compares the decompressed length with the first 8 bytes of the
+	/// trailer. Files precompressed by --precompress are not affected.
+	#[clap(long, value_name = "codec", requires = "output")]
+	aligned_compressed: Option<String>,
+	/// Keeps extra fields of files.
+	///
+	/// Keeps extra fields of files in input ZIP archives like high-resolution timestamps and Unix
+	/// ownership. Files with extra fields are recompressed rather than copied as is, since only
+	/// recompression allows writing extra fields. Extra fields which are rewritten, like ZIP64
+	/// sizes and alignment padding, or invalidated by renaming, like Unicode paths, are dropped.
+	/// Extra fields describing the compressed data itself do not survive recompression. Stacked
+	/// arrays and directories have no extra fields.
+	#[clap(long)]
+	keep_extra_fields: bool,
+	/// Sets Unix owner of files.
+	///
+	/// Writes the Info-ZIP Unix extra field with the given UID. The GID is taken from --group, the
+	/// file with --preserve-owner, or is 0 otherwise. Files with ownership are recompressed rather
+	/// than copied as is. Directories have no extra fields and hence no ownership.
+	#[clap(long, value_name = "uid")]
+	owner: Option<u32>,
+	/// Sets Unix group of files.
+	///
+	/// Writes the Info-ZIP Unix extra field with the given GID like --owner does with the UID.
+	#[clap(long, value_name = "gid")]
+	group: Option<u32>,
+	/// Preserves Unix owner and group of files.
+	///
+	/// Takes UID and GID from the metadata of input files, the headers of input TAR archives, or
+	/// the Info-ZIP Unix extra fields of files in input ZIP archives, unless overridden by --owner
+	/// or --group. Input files have no ownership on non-Unix hosts where only --owner and --group
+	/// apply.
+	#[clap(long)]
+	preserve_owner: bool,
+	/// Aligns uncompressed files.
+	///
+	/// Aligns uncompressed files in ZIP archives by padding local file headers to enable
+	/// memory-mapping, SIMD instruction extensions like AVX-512, and dynamic loading of shared
+	/// objects.
+	#[clap(short, long, value_name = "[glob=]bytes", default_values = DEFAULT_ALIGN)]
+	align: Vec<String>,
+	/// Aligns compressed files too.
+	///
+	/// Aligns the start of the compressed data of compressed files as well for loaders mapping it
+	/// into memory before decompressing it. The decompressed data itself is not aligned. Checks
+	/// the alignment of compressed files too with no output ZIP archive.
+	#[clap(long, visible_alias = "align-compressed")]
+	align_all: bool,
+	/// Allows alignments other than powers of two.
+	///
+	/// Accepts any --align bytes from 1 to 65535, like 48 or 96 for loaders of packed records,
+	/// instead of powers of two only.
+	#[clap(long)]
+	align_arbitrary: bool,
+	/// Stacks arrays along axis.
+	///
+	/// Stacks identically named NPY files case-insensitively by extension. Members of input NPZ
+	/// archives are stacked with their counterparts in other input NPZ archives, resulting in an
+	/// output NPZ archive of stacked members. Globs match NPY files with and without extension,
+	/// so members of input NPZ archives match by their array names as in --stack 'images=0'
+	/// --stack 'labels=1', including their paths as in --stack 'weights/*=0'. Identically named
+	/// NPY files matching no globs are merged as selected by --collision, the one of the last given
+	/// input NPZ archive by default, since passing globs replaces the default of stacking all
+	/// along axis 0. Supports booleans, integers, and floats of single and double precision, and
+	/// of half precision if built with the half feature. Gzipped NPY files with .npy.gz extensions
+	/// are decompressed before stacking and written decompressed without their .gz extensions
+	/// unless --stack-gzip is given.
+	///
+	/// Stacks identically named CSV files case-insensitively by extension along axis 0 only by
+	/// concatenating their records. Rows are rewritten with minimal quoting and LF terminators
+	/// as they are parsed and not copied verbatim.
+	///
+	/// Stacks identically named JSON files case-insensitively by extension along axis 0 only by
+	/// concatenating the elements of their top-level arrays or by merging the keys of their
+	/// top-level objects as selected by --json-merge. Values are rewritten compactly as they are
+	/// parsed.
+	///
+	/// Stacks identically named Parquet files case-insensitively by extension along axis 0 only
+	/// by concatenating their row groups if built with the parquet feature. Requires the schemas
+	/// of stacked Parquet files to have identical fields and keeps the metadata of the first one.
+	/// Each input is spilled to a temporary file for random access, while the written Parquet file
+	/// is buffered as a whole, uncompressed, in memory.
+	///
+	/// One stacked array at a time must fit into memory before it is written to the output ZIP
+	/// archive, along with one of its input arrays when stacking along axis 0, as each is read
+	/// into the preallocated stacked array in turn, or with all of them otherwise.
+	#[clap(short, long, value_name = "[glob=]axis", default_values = DEFAULT_STACK)]
+	stack: Vec<String>,
+	/// Broadcasts lower-dimensional arrays before stacking.
+	///
+	/// Broadcasts arrays of fewer dimensions, like scalars, to the common shape of the arrays of
+	/// the most dimensions with a length of one along the stack axis. Broadcasting follows NumPy by
+	/// prepending axes of length one and repeating axes of length one. Arrays of the most
+	/// dimensions must agree in all but the stack axis for the common shape to be unambiguous.
+	#[clap(long)]
+	stack_broadcast: bool,
+	/// Flattens arrays before stacking.
+	///
+	/// Ravels each array to be stacked in C order to one dimension and concatenates them into
+	/// one-dimensional arrays regardless of the stack axis, like per-shard scalars into an array
+	/// of them. The shapes of the arrays are lost, so their lengths may differ.
+	#[clap(long, conflicts_with = "stack-broadcast")]
+	flatten_stack: bool,
+	/// Gzips stacked NPY files again.
+	///
+	/// Writes stacked .npy.gz files gzipped again keeping their names instead of decompressed
+	/// without their .gz extensions.
+	#[clap(long)]
+	stack_gzip: bool,
+	/// Stacks NPY files cast to a common data-type.
+	///
+	/// Reads the arrays to be stacked as the given data-type instead of requiring them to agree,
+	/// as in --stack-dtype f64 stacking arrays of single and double precision. Fails unless the
+	/// data-type of each array is represented by the given one without loss, like integers of up
+	/// to 16 bits by single precision and of up to 32 bits by double precision. Half precision,
+	/// available as f16 if built with the half feature, is cast to single and double precision,
+	/// whereas nothing is cast to it.
+	#[clap(long, value_name = "dtype", value_enum)]
+	stack_dtype: Option<StackDtype>,
+	/// Stacks NPY files of differing data-types by listed casts.
+	///
+	/// Allows the given casts only, as in --stack-cast 'i32->i64,f32->f64', instead of requiring
+	/// the arrays to be stacked to agree in data-type. Casts them to the one data-type all others
+	/// are listed to be cast to, failing with the first array lacking a listed cast otherwise.
+	/// Arrays agreeing in data-type are stacked as is. Only casts representing each value without
+	/// loss are accepted, whereas narrowing casts, like i64->i32 or i32->f32, are never applied.
+	#[clap(
+		long,
+		value_name = "dtype->dtype",
+		value_delimiter = ',',
+		parse(try_from_str = parse_stack_cast),
+		conflicts_with = "stack-dtype"
+	)]
+	stack_cast: Vec<(StackDtype, StackDtype)>,
+	/// Writes stacked NPY files in memory order.
+	///
+	/// Writes stacked arrays in C (row-major) or Fortran (column-major) order. Arrays of either
+	/// order are read and stacked by their logical indices, so inputs may mix orders, while the
+	/// order of the output is explicit instead of following the inputs.
+	#[clap(long, value_name = "order", value_enum, default_value = "c")]
+	npy_order: NpyOrder,
+	/// Reads raw files as NPY files described by JSON file.
+	///
+	/// Reads the files matching the globs as headerless arrays described by the given JSON file,
+	/// as in --raw-spec '*.bin=spec.json' with {"shape": [480, 640], "dtype": "u8"} and optional
+	/// "fortran_order": true, and writes them as NPY files with .npy extensions instead, which
+	/// are stacked like others. The data-type is given by name as for --stack-dtype or as NumPy
+	/// type string like >f4 for big-endian data, whereas names assume little-endian data. Fails
+	/// unless the size of each file matches the one described.
+	#[clap(long, value_name = "glob=path", conflicts_with = "stack-by-index")]
+	raw_spec: Vec<String>,
+	/// Stacks files by external command.
+	///
+	/// Stacks identically named files matching the globs of any format by running the command
+	/// split at whitespace with the paths of temporary files appended as arguments, one per file
+	/// in stacking order with the extension of its name, and writing its standard output as the
+	/// stacked file, as in --stack-cmd '*.pb=protostack --merge'. Splits at the first = unlike
+	/// other options, so the command may contain = as in --stack-cmd '*.pb=protostack --level=3'
+	/// whereas the glob may not. Takes precedence over stacking supported formats natively. Fails
+	/// if the command exits unsuccessfully.
+	#[clap(long, value_name = "glob=command")]
+	stack_cmd: Vec<String>,
+	/// Sorts stacked files.
+	///
+	/// Stacks identically named files sorted by the paths of their input ZIP archives, by them in
+	/// natural order comparing runs of digits by their numeric values as in part-2.npy before
+	/// part-10.npy, or by their last modification times instead of in the given order. Ties keep
+	/// the given order.
+	#[clap(long, value_name = "order", value_enum)]
+	stack_order: Option<StackOrder>,
+	/// Sorts stacked files by index arrays.
+	///
+	/// Stacks identically named files sorted by the integer of the NPY file of the given name in
+	/// their input ZIP archives, as in --stack-by-index order.npy for shards carrying their own
+	/// position. The NPY file must hold a scalar or a single integer. Ties keep the given order.
+	/// Fails if an input ZIP archive of stacked files lacks the NPY file.
+	#[clap(long, value_name = "name", conflicts_with = "stack-order")]
+	stack_by_index: Option<PathBuf>,
+	/// Handles arrays missing in some input ZIP archives.
+	///
+	/// Fails, merges instead of stacking, or stacks zero-filled arrays for NPY files to be stacked
+	/// but missing in some of the input ZIP archives containing other NPY files to be stacked,
+	/// preventing the silently smaller stacks of stacking the present ones only by default.
+	/// Zero-filled arrays take the shape and data-type of the first present one and the places of
+	/// the missing ones in the given order, so --stack-order and --stack-by-index are not
+	/// supported.
+	#[clap(long, value_name = "policy", value_enum)]
+	on_missing_stack_member: Option<MissingPolicy>,
+	/// Fails on more identically named files to be stacked than given.
+	///
+	/// Counts the files to be stacked by name after indexing all input ZIP archives before
+	/// reading any of them, guarding against globs accidentally matching too many input ZIP
+	/// archives. Stacks of any size are allowed by default.
+	#[clap(long, value_name = "files")]
+	max_stack_inputs: Option<usize>,
+	/// Treats first rows of CSV files as headers.
+	///
+	/// Requires the headers of stacked CSV files to be identical and writes them once followed by
+	/// the records of each file in the given order. Otherwise, all rows are records.
+	#[clap(
+		long,
+		value_name = "bool",
+		default_value = "true",
+		parse(try_from_str),
+		require_equals = true
+	)]
+	csv_header: bool,
+	/// Merges stacked JSON files by top level.
+	///
+	/// Requires the top levels of stacked JSON files to be arrays and concatenates their
+	/// elements with array or to be objects and merges their keys shallowly with object, with
+	/// values of later files superseding those of identical keys in earlier ones, keeping the
+	/// position of the key first seen.
+	#[clap(long, value_name = "array|object", value_enum, default_value = "array")]
+	json_merge: JsonMerge,
+	/// Enables zstd long-distance matching.
+	///
+	/// Recompresses zstd files with long-distance matching within a window of 2^window_log bytes,
+	/// 2^27 bytes (128 MiB) by default, improving the ratio of large files containing repetitions
+	/// far apart. Decompressing needs as much memory for the window. Window logs range from 10 to
+	/// 27, as standard zstd decoders reject larger windows by default. Files are compressed into a
+	/// temporary file first and then copied, so they are neither aligned nor have extra fields.
+	/// Checks window logs instead of levels with no output ZIP archive.
+	#[clap(
+		long,
+		value_name = "window_log",
+		min_values = 0,
+		require_equals = true,
+		default_missing_value = "27"
+	)]
+	zstd_long: Option<u32>,
+	/// Compresses zstd files with worker threads.
+	///
+	/// Recompresses zstd files with the given count of worker threads compressing parts of a file
+	/// in parallel, speeding up large files as files themselves are written one after another.
+	/// Zero workers compress on the calling thread. Otherwise, the compressed data may differ from
+	/// the one of zero workers. Files are compressed into a temporary file first and then copied
+	/// like with --zstd-long.
+	#[clap(long, value_name = "count", default_value = "0", requires = "output")]
+	zstd_threads: u32,
+	/// Caps compression levels.
+	///
+	/// Lowers the levels given by --recompress exceeding the given level, bounding the time spent
+	/// compressing without editing every method. The cap is clamped into the range of levels of
+	/// each algorithm, so a cap of 0 lowers bzip2 and zstd levels to 1. Default levels of methods
+	/// without a level are kept. Clamping is logged at debug level.
+	#[clap(long, value_name = "level")]
+	max_level: Option<i32>,
+	/// Caches recompressed files in directory.
+	///
+	/// Reuses the compressed data of files recompressed before with the same method and level
+	/// instead of recompressing them, keyed by the BLAKE3 hash of their uncompressed data, method,
+	/// and level. Stacked, precompressed, aligned, and zstd files of --zstd-long or --zstd-threads
+	/// are not cached, neither are files with extra fields. Costs reading each cached file once
+	/// more. Entries are never invalidated but evicted, so delete the directory after upgrading
+	/// rezip to recompress with possibly changed compressors.
+	#[clap(long, value_name = "path", requires = "output")]
+	cache_dir: Option<PathBuf>,
+	/// Limits cache size.
+	///
+	/// Evicts the least recently used entries of --cache-dir after writing the output ZIP archive
+	/// until their total size does not exceed the given bytes.
+	#[clap(
+		long,
+		value_name = "bytes",
+		default_value = "1GiB",
+		parse(try_from_str = parse_size),
+		requires = "cache-dir"
+	)]
+	cache_size: u64,
+	/// Disables --cache-dir.
+	#[clap(long)]
+	no_cache: bool,
+	/// Logs status information to stderr.
+	///
+	/// The more occurrences, the more verbose, with three at most, logging at info, debug, or trace
+	/// level. Overrides the level of rezip otherwise filtered by the RUST_LOG environment variable
+	/// as in RUST_LOG=rezip=debug, defaulting to warn level. Reports the wall-clock time spent
+	/// indexing and compressing per input ZIP archive and in total along with stacking and the
+	/// throughput at debug level. Keeps stdout free for data like the listing of --list.
+	#[clap(short, long, parse(from_occurrences))]
+	verbose: u64,
+	/// Prints errors only.
+	///
+	/// Suppresses status information and warnings, overriding --verbose.
+	#[clap(short, long)]
+	quiet: bool,
+	/// Logs status information formatted.
+	///
+	/// Logs text timestamped and leveled or prints JSON objects, one per line with an "event" field.
+	#[clap(long, value_name = "format", value_enum, default_value = "text")]
+	log_format: LogFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ChecksumAlgorithm {
+	Sha256,
+	Blake3,
+}
+
+impl ChecksumAlgorithm {
+	fn extension(self) -> &'static str {
+		match self {
+			Self::Sha256 => "sha256",
+			Self::Blake3 => "blake3",
+		}
+	}
+}
+
+enum Hasher {
+	Sha256(Sha256),
+	Blake3(blake3::Hasher),
+}
+
+impl Hasher {
+	fn new(algorithm: ChecksumAlgorithm) -> Self {
+		match algorithm {
+			ChecksumAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+			ChecksumAlgorithm::Blake3 => Self::Blake3(blake3::Hasher::new()),
+		}
+	}
+	fn update(&mut self, buf: &[u8]) {
+		match self {
+			Self::Sha256(hasher) => hasher.update(buf),
+			Self::Blake3(hasher) => {
+				hasher.update(buf);
+			}
+		}
+	}
+	fn finalize(self) -> String {
+		match self {
+			Self::Sha256(hasher) => hasher
+				.finalize()
+				.iter()
+				.map(|byte| format!("{:02x}", byte))
+				.collect(),
+			Self::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+		}
+	}
+}
+
+struct Checksum<W: Write> {
+	writer: W,
+	hasher: Option<Hasher>,
+	length: u64,
+}
+
+impl<W: Write> Checksum<W> {
+	fn new(writer: W, algorithm: Option<ChecksumAlgorithm>) -> Self {
+		Self {
+			writer,
+			hasher: algorithm.map(Hasher::new),
+			length: 0,
+		}
+	}
+	fn finalize(self) -> (Option<String>, u64) {
+		(self.hasher.map(Hasher::finalize), self.length)
+	}
+}
+
+impl<W: Write> Write for Checksum<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let length = self.writer.write(buf)?;
+		if let Some(hasher) = &mut self.hasher {
+			hasher.update(&buf[..length]);
+		}
+		self.length += length as u64;
+		Ok(length)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+// Codec of --precompress or --aligned-compressed with its level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Precompression {
+	Xz(u32),
+	Zstd(i32),
+}
+
+// Writer compressing data with the codec of --precompress or --aligned-compressed, if any.
+enum Precompressor<W: Write> {
+	None(W),
+	Xz(XzEncoder<W>),
+	Zstd(zstd::Encoder<'static, W>, u64),
+}
+
+impl<W: Write> Precompressor<W> {
+	fn new(writer: W, codec: Option<Precompression>) -> io::Result<Self> {
+		Ok(match codec {
+			Some(Precompression::Xz(level)) => Self::Xz(XzEncoder::new(writer, level)),
+			Some(Precompression::Zstd(level)) => Self::Zstd(zstd::Encoder::new(writer, level)?, 0),
+			None => Self::None(writer),
+		})
+	}
+	fn finish(self) -> io::Result<W> {
+		match self {
+			Self::None(writer) => Ok(writer),
+			Self::Xz(encoder) => encoder.finish(),
+			Self::Zstd(encoder, length) => {
+				let mut writer = encoder.finish()?;
+				writer.write_all(&length.to_le_bytes())?;
+				writer.write_all(b"zstdRZIP")?;
+				Ok(writer)
+			}
+		}
+	}
+}
+
+impl<W: Write> Write for Precompressor<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self {
+			Self::None(writer) => writer.write(buf),
+			Self::Xz(encoder) => encoder.write(buf),
+			Self::Zstd(encoder, length) => {
+				let written = encoder.write(buf)?;
+				*length += written as u64;
+				Ok(written)
+			}
+		}
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		match self {
+			Self::None(writer) => writer.flush(),
+			Self::Xz(encoder) => encoder.flush(),
+			Self::Zstd(encoder, _length) => encoder.flush(),
+		}
+	}
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CollisionPolicy {
+	Last,
+	First,
+	Newest,
+	Largest,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum InputOrder {
+	Asis,
+	Name,
+	Natural,
+	Mtime,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum StackOrder {
+	Name,
+	Natural,
+	Mtime,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum NpyOrder {
+	C,
+	F,
+}
+
+// Reshapes arrays before stacking them as requested by --stack-broadcast or --flatten-stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StackShape {
+	Keep,
+	Broadcast,
+	Flatten,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum StackDtype {
+	Bool,
+	U8,
+	I8,
+	U16,
+	I16,
+	U32,
+	I32,
+	U64,
+	I64,
+	#[cfg(feature = "half")]
+	F16,
+	F32,
+	F64,
+}
+
+impl StackDtype {
+	fn from_descr(descr: &str) -> Option<Self> {
+		let descr = descr.strip_prefix(['<', '>', '=', '|']).unwrap_or(descr);
+		Some(match descr {
+			"b1" => Self::Bool,
+			"u1" => Self::U8,
+			"i1" => Self::I8,
+			"u2" => Self::U16,
+			"i2" => Self::I16,
+			"u4" => Self::U32,
+			"i4" => Self::I32,
+			"u8" => Self::U64,
+			"i8" => Self::I64,
+			#[cfg(feature = "half")]
+			"f2" => Self::F16,
+			"f4" => Self::F32,
+			"f8" => Self::F64,
+			_ => return None,
+		})
+	}
+	fn name(self) -> &'static str {
+		self.to_possible_value().unwrap().get_name()
+	}
+	fn descr(self) -> &'static str {
+		match self {
+			Self::Bool => "|b1",
+			Self::U8 => "|u1",
+			Self::I8 => "|i1",
+			Self::U16 => "<u2",
+			Self::I16 => "<i2",
+			Self::U32 => "<u4",
+			Self::I32 => "<i4",
+			Self::U64 => "<u8",
+			Self::I64 => "<i8",
+			#[cfg(feature = "half")]
+			Self::F16 => "<f2",
+			Self::F32 => "<f4",
+			Self::F64 => "<f8",
+		}
+	}
+	// Kind and bits of the data-type with booleans as unsigned integers of one bit.
+	fn kind(self) -> (char, u32) {
+		match self {
+			Self::Bool => ('u', 1),
+			Self::U8 => ('u', 8),
+			Self::I8 => ('i', 8),
+			Self::U16 => ('u', 16),
+			Self::I16 => ('i', 16),
+			Self::U32 => ('u', 32),
+			Self::I32 => ('i', 32),
+			Self::U64 => ('u', 64),
+			Self::I64 => ('i', 64),
+			#[cfg(feature = "half")]
+			Self::F16 => ('f', 16),
+			Self::F32 => ('f', 32),
+			Self::F64 => ('f', 64),
+		}
+	}
+	// Whether all values of the data-type are represented by the other one. Floats represent
+	// integers of up to half their bits. Half precision is cast from only, as arrays of it are
+	// not written by ndarray-npy.
+	fn widens_to(self, other: Self) -> bool {
+		let ((kind, bits), (other_kind, other_bits)) = (self.kind(), other.kind());
+		match (kind, other_kind) {
+			#[cfg(feature = "half")]
+			_ if other == Self::F16 => false,
+			('u', 'u' | 'i') | ('i', 'i') | ('f', 'f') => bits < other_bits,
+			('u' | 'i', 'f') => bits <= other_bits / 2,
+			_ => false,
+		}
+	}
+}
+
+fn parse_stack_cast(cast: &str) -> Result<(StackDtype, StackDtype), String> {
+	let (from, to) = cast
+		.split_once("->")
+		.ok_or_else(|| format!("Invalid cast {:?}", cast))?;
+	let from = StackDtype::from_str(from, true)?;
+	let to = StackDtype::from_str(to, true)?;
+	if from.widens_to(to) {
+		Ok((from, to))
+	} else {
+		Err(format!(
+			"Cannot cast {} to {} losslessly",
+			from.name(),
+			to.name()
+		))
+	}
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Zip64Mode {
+	Always,
+	Never,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum TimestampSource {
+	Source,
+	Now,
+	Fixed(DateTime),
+	Max,
+}
+
+fn parse_timestamp_source(source: &str) -> Result<TimestampSource, String> {
+	match source {
+		"source" => Ok(TimestampSource::Source),
+		"now" => Ok(TimestampSource::Now),
+		"max" => Ok(TimestampSource::Max),
+		_ => {
+			let time = source
+				.strip_prefix("fixed=")
+				.ok_or_else(|| format!("Invalid timestamp source {:?}", source))?;
+			OffsetDateTime::parse(time, &Iso8601::DEFAULT)
+				.map_err(|err| err.to_string())
+				.and_then(|utc| {
+					DateTime::try_from(utc.to_offset(UtcOffset::UTC))
+						.map_err(|_err| format!("Time {:?} out of range", time))
+				})
+				.map(TimestampSource::Fixed)
+		}
+	}
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum JsonMerge {
+	Array,
+	Object,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum TouchOutput {
+	Now,
+	Source,
+	Fixed(OffsetDateTime),
+}
+
+fn parse_touch_output(touch: &str) -> Result<TouchOutput, String> {
+	match touch {
+		"now" => Ok(TouchOutput::Now),
+		"source" => Ok(TouchOutput::Source),
+		_ => OffsetDateTime::parse(touch, &Iso8601::DEFAULT)
+			.map(TouchOutput::Fixed)
+			.map_err(|err| err.to_string()),
+	}
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum MissingPolicy {
+	Error,
+	Skip,
+	Zero,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DedupMode {
+	Drop,
+	Report,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+	Text,
+	Json,
+}
+
+struct Log {
+	format: LogFormat,
+}
+
+impl Log {
+	fn event(&self, event: RezipEvent) {
+		let level = event.level();
+		match self.format {
+			LogFormat::Text => log!(level, "{}", event),
+			LogFormat::Json => {
+				if log_enabled!(level) {
+					eprintln!("{}", event.to_json());
+				}
+			}
+		}
+	}
+}
+
+// Wall-clock time and written bytes per phase and input reported at -vv verbosity. Stacked files
+// are read from all their inputs at once, so stacking is timed in total only.
+#[derive(Default)]
+struct Timings {
+	indexing: HashMap<usize, Duration>,
+	compressing: HashMap<usize, (Duration, u64)>,
+	stacking: (Duration, u64),
+}
+
+impl Timings {
+	fn written(&mut self, input: usize, stacked: bool, elapsed: Duration, bytes: u64) {
+		let (time, length) = if stacked {
+			&mut self.stacking
+		} else {
+			self.compressing.entry(input).or_default()
+		};
+		*time += elapsed;
+		*length += bytes;
+	}
+}
+
+/// Failure of checking input ZIP archives with no output ZIP archive.
+///
+/// Is returned by [`Rezip::run()`] wrapped in a [`Report`] for files not compressed or aligned as
+/// requested, see [`Report::downcast_ref()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckError {
+	/// Files are aligned but not compressed as requested.
+	NotCompressed,
+	/// Files are compressed but not aligned as requested.
+	NotAligned,
+	/// Files are neither compressed nor aligned as requested.
+	NeitherCompressedNorAligned,
+}
+
+impl fmt::Display for CheckError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::NotCompressed => write!(f, "Not compressed but aligned as requested"),
+			Self::NotAligned => write!(f, "Compressed but not aligned as requested"),
+			Self::NeitherCompressedNorAligned => {
+				write!(f, "Not compressed nor aligned as requested")
+			}
+		}
+	}
+}
+
+impl std::error::Error for CheckError {}
+
+/// Status information passed to the callback of [`Rezip::run_with_progress()`].
+pub enum RezipEvent<'a> {
+	/// Indexing files of input ZIP archive.
+	Indexing {
+		/// Input ZIP archive.
+		from: &'a Path,
+		/// Number of files.
+		files: usize,
+	},
+	/// Merging directory into output ZIP archive.
+	MergingDirectory {
+		/// Name of directory.
+		name: &'a Path,
+		/// Input ZIP archive.
+		from: &'a Path,
+	},
+	/// Copying file without recompression.
+	Copying {
+		/// Name of file.
+		name: &'a Path,
+		/// Compression method.
+		method: String,
+		/// Input ZIP archive.
+		from: &'a Path,
+	},
+	/// Starting aligned file.
+	Aligning {
+		/// Name of file.
+		name: &'a Path,
+		/// Requested alignment.
+		bytes: u16,
+	},
+	/// Padding local file header of aligned file.
+	Padding {
+		/// Name of file.
+		name: &'a Path,
+		/// Length of padding.
+		bytes: u64,
+	},
+	/// Starting recompressed file.
+	Recompressing {
+		/// Name of file.
+		name: &'a Path,
+		/// Compression method with optional level.
+		method: String,
+	},
+	/// Clamped compression level of --max-level.
+	Clamping {
+		/// Name of file.
+		name: &'a Path,
+		/// Compression method with level.
+		method: String,
+		/// Clamped level.
+		level: i32,
+	},
+	/// Selected compression method of --recompress auto.
+	Selecting {
+		/// Name of file.
+		name: &'a Path,
+		/// Compression method.
+		method: String,
+		/// Reason of selection.
+		reason: &'static str,
+	},
+	/// Stacking arrays of files.
+	Stacking {
+		/// Name of file.
+		name: &'a Path,
+		/// Number of stacked files.
+		files: usize,
+	},
+	/// Stacking array of file in input ZIP archive.
+	StackingFrom {
+		/// Name of file.
+		name: &'a Path,
+		/// Input ZIP archive.
+		from: &'a Path,
+	},
+	/// Adding directory missing in input ZIP archives.
+	AddingDirectory {
+		/// Name of directory.
+		name: &'a Path,
+	},
+	/// Merging file into output ZIP archive.
+	Merging {
+		/// Name of file.
+		name: &'a Path,
+		/// Input ZIP archive.
+		from: &'a Path,
+	},
+	/// Excluding file by its size or by --strip-components.
+	Excluding {
+		/// Name of file.
+		name: &'a Path,
+		/// Input ZIP archive.
+		from: &'a Path,
+		/// Uncompressed size.
+		bytes: u64,
+	},
+	/// Written file into output ZIP archive.
+	Written {
+		/// Name of file.
+		name: &'a Path,
+		/// Length of uncompressed data.
+		bytes: u64,
+	},
+	/// Finishing output ZIP archive or one of its volumes.
+	Finishing {
+		/// Output ZIP archive.
+		path: &'a Path,
+		/// Volume the output ZIP archive is renamed to.
+		volume: Option<&'a Path>,
+	},
+	/// Padded local file headers in total.
+	TotalPadding {
+		/// Output ZIP archive.
+		path: &'a Path,
+		/// Total length of paddings.
+		bytes: u64,
+	},
+	/// Detected file of identical content.
+	Duplicate {
+		/// Name of file.
+		name: &'a Path,
+		/// Name of formerly written file.
+		of: &'a Path,
+		/// Whether the file is dropped.
+		dropped: bool,
+	},
+	/// Detected files of identical content in total.
+	TotalDuplicates {
+		/// Output ZIP archive.
+		path: &'a Path,
+		/// Number of files.
+		files: usize,
+		/// Total length of their uncompressed data.
+		bytes: u64,
+		/// Whether the files are dropped.
+		dropped: bool,
+	},
+	/// Compared file with identically named one of --diff-against.
+	Changed {
+		/// Name of file.
+		name: &'a Path,
+		/// Whether the file is added, removed, modified, or unchanged.
+		change: &'static str,
+	},
+	/// Compared files with the ones of --diff-against in total.
+	TotalChanges {
+		/// Output ZIP archive.
+		path: &'a Path,
+		/// Previous ZIP archive.
+		against: &'a Path,
+		/// Number of added files.
+		added: usize,
+		/// Number of removed files.
+		removed: usize,
+		/// Number of modified files.
+		modified: usize,
+		/// Number of unchanged files.
+		unchanged: usize,
+	},
+	/// Checked compression of file.
+	Compressed {
+		/// Name of file.
+		name: &'a Path,
+		/// Compression method with optional level.
+		method: String,
+		/// Input ZIP archive.
+		from: &'a Path,
+		/// Whether the file is compressed as requested.
+		requested: bool,
+	},
+	/// Checked alignment of file.
+	Aligned {
+		/// Name of file.
+		name: &'a Path,
+		/// Requested alignment.
+		bytes: u16,
+		/// Input ZIP archive.
+		from: &'a Path,
+		/// Whether the file is aligned as requested.
+		requested: bool,
+	},
+	/// Reported compression method of file.
+	Reported {
+		/// Name of file.
+		name: &'a Path,
+		/// Compression method with inferred level.
+		method: String,
+		/// Input ZIP archive.
+		from: &'a Path,
+	},
+	/// Numbering name of identically named file.
+	Numbering {
+		/// Name of file.
+		name: &'a Path,
+		/// Numbered name of file.
+		to: &'a Path,
+		/// Input ZIP archive.
+		from: &'a Path,
+	},
+	/// Renaming file to the hash of its content.
+	Addressing {
+		/// Name of file.
+		name: &'a Path,
+		/// Content-addressed name of file.
+		to: &'a Path,
+	},
+	/// Caching compressed file or reusing cached one.
+	Caching {
+		/// Name of file.
+		name: &'a Path,
+		/// Compression method with optional level.
+		method: String,
+		/// Whether the cached file is reused.
+		reused: bool,
+	},
+	/// Handling NPY file missing in input ZIP archive according to --on-missing-stack-member.
+	Missing {
+		/// Name of file.
+		name: &'a Path,
+		/// Input ZIP archive.
+		from: &'a Path,
+		/// Whether a zero-filled array is stacked instead or the present ones are merged.
+		zeroed: bool,
+	},
+	/// Extracting file into directory.
+	Extracting {
+		/// Name of file.
+		name: &'a Path,
+		/// Path of extracted file.
+		to: &'a Path,
+	},
+	/// Failed to decompress file while verifying.
+	Corrupted {
+		/// Name of file.
+		name: &'a Path,
+		/// Input ZIP archive.
+		from: &'a Path,
+		/// Cause of failure.
+		error: String,
+	},
+	/// Spent wall-clock time in phase.
+	Timing {
+		/// Output ZIP archive.
+		path: &'a Path,
+		/// Phase of indexing, compressing, stacking, or total.
+		phase: &'static str,
+		/// Input ZIP archive or none for the total of all of them.
+		from: Option<&'a Path>,
+		/// Elapsed seconds.
+		seconds: f64,
+		/// Length of uncompressed data written.
+		bytes: u64,
+	},
+	/// Checked all files to be compressed and aligned as requested.
+	Checked,
+}
+
+impl RezipEvent<'_> {
+	fn level(&self) -> Level {
+		match self {
+			Self::Compressed {
+				requested: true, ..
+			}
+			| Self::Aligned {
+				requested: true, ..
+			} => Level::Debug,
+			Self::Padding { .. }
+			| Self::TotalPadding { .. }
+			| Self::Clamping { .. }
+			| Self::Selecting { .. }
+			| Self::Timing { .. } => Level::Debug,
+			Self::StackingFrom { .. } | Self::Written { .. } => Level::Trace,
+			Self::Changed {
+				change: "unchanged",
+				..
+			} => Level::Debug,
+			Self::TotalDuplicates { .. } | Self::TotalChanges { .. } | Self::Corrupted { .. } => {
+				Level::Warn
+			}
+			_ => Level::Info,
+		}
+	}
+	fn to_json(&self) -> Value {
+		let path = |path: &Path| path.to_string_lossy().into_owned();
+		match self {
+			Self::Indexing { from, files } => {
+				json!({ "event": "indexing", "from": path(from), "files": files })
+			}
+			Self::MergingDirectory { name, from } => {
+				json!({ "event": "merging_directory", "name": path(name), "from": path(from) })
+			}
+			Self::Copying { name, method, from } => {
+				json!({ "event": "copying", "name": path(name), "method": method, "from": path(from) })
+			}
+			Self::Aligning { name, bytes } => {
+				json!({ "event": "aligning", "name": path(name), "bytes": bytes })
+			}
+			Self::Padding { name, bytes } => {
+				json!({ "event": "padding", "name": path(name), "bytes": bytes })
+			}
+			Self::Recompressing { name, method } => {
+				json!({ "event": "recompressing", "name": path(name), "method": method })
+			}
+			Self::Clamping {
+				name,
+				method,
+				level,
+			} => {
+				json!({ "event": "clamping", "name": path(name), "method": method, "level": level })
+			}
+			Self::Selecting {
+				name,
+				method,
+				reason,
+			} => {
+				json!({ "event": "selecting", "name": path(name), "method": method, "reason": reason })
+			}
+			Self::Stacking { name, files } => {
+				json!({ "event": "stacking", "name": path(name), "files": files })
+			}
+			Self::StackingFrom { name, from } => {
+				json!({ "event": "stacking_from", "name": path(name), "from": path(from) })
+			}
+			Self::Merging { name, from } => {
+				json!({ "event": "merging", "name": path(name), "from": path(from) })
+			}
+			Self::Excluding { name, from, bytes } => {
+				json!({ "event": "excluding", "name": path(name), "from": path(from), "bytes": bytes })
+			}
+			Self::Written { name, bytes } => {
+				json!({ "event": "written", "name": path(name), "bytes": bytes })
+			}
+			Self::AddingDirectory { name } => {
+				json!({ "event": "adding_directory", "name": path(name) })
+			}
+			Self::Finishing {
+				path: output,
+				volume,
+			} => json!({
+				"event": "finishing",
+				"path": path(output),
+				"volume": volume.map(path),
+			}),
+			Self::TotalPadding {
+				path: output,
+				bytes,
+			} => {
+				json!({ "event": "total_padding", "path": path(output), "bytes": bytes })
+			}
+			Self::Duplicate { name, of, dropped } => json!({
+				"event": "duplicate",
+				"name": path(name),
+				"of": path(of),
+				"dropped": dropped,
+			}),
+			Self::TotalDuplicates {
+				path: output,
+				files,
+				bytes,
+				dropped,
+			} => json!({
+				"event": "total_duplicates",
+				"path": path(output),
+				"files": files,
+				"bytes": bytes,
+				"dropped": dropped,
+			}),
+			Self::Changed { name, change } => {
+				json!({ "event": "changed", "name": path(name), "change": change })
+			}
+			Self::TotalChanges {
+				path: output,
+				against,
+				added,
+				removed,
+				modified,
+				unchanged,
+			} => json!({
+				"event": "total_changes",
+				"path": path(output),
+				"against": path(against),
+				"added": added,
+				"removed": removed,
+				"modified": modified,
+				"unchanged": unchanged,
+			}),
+			Self::Compressed {
+				name,
+				method,
+				from,
+				requested,
+			} => json!({
+				"event": "compressed",
+				"name": path(name),
+				"method": method,
+				"from": path(from),
+				"requested": requested,
+			}),
+			Self::Aligned {
+				name,
+				bytes,
+				from,
+				requested,
+			} => json!({
+				"event": "aligned",
+				"name": path(name),
+				"bytes": bytes,
+				"from": path(from),
+				"requested": requested,
+			}),
+			Self::Caching {
+				name,
+				method,
+				reused,
+			} => {
+				json!({ "event": "caching", "name": path(name), "method": method, "reused": reused })
+			}
+			Self::Missing { name, from, zeroed } => {
+				json!({ "event": "missing", "name": path(name), "from": path(from), "zeroed": zeroed })
+			}
+			Self::Extracting { name, to } => {
+				json!({ "event": "extracting", "name": path(name), "to": path(to) })
+			}
+			Self::Reported { name, method, from } => json!({
+				"event": "reported",
+				"name": path(name),
+				"method": method,
+				"from": path(from),
+			}),
+			Self::Numbering { name, to, from } => json!({
+				"event": "numbering",
+				"name": path(name),
+				"to": path(to),
+				"from": path(from),
+			}),
+			Self::Addressing { name, to } => {
+				json!({ "event": "addressing", "name": path(name), "to": path(to) })
+			}
+			Self::Corrupted { name, from, error } => json!({
+				"event": "corrupted",
+				"name": path(name),
+				"from": path(from),
+				"error": error,
+			}),
+			Self::Timing {
+				path: output,
+				phase,
+				from,
+				seconds,
+				bytes,
+			} => json!({
+				"event": "timing",
+				"path": path(output),
+				"phase": phase,
+				"from": from.map(path),
+				"seconds": seconds,
+				"bytes": bytes,
+			}),
+			Self::Checked => json!({ "event": "checked" }),
+		}
+	}
+}
+
+impl fmt::Display for RezipEvent<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Indexing { from, files } => {
+				let plural = if *files > 1 { "s" } else { "" };
+				write!(f, "{:?}: indexing {} file{}", from, files, plural)
+			}
+			Self::MergingDirectory { name, from } => {
+				write!(f, "{:?}: merging directory from {:?}", name, from)
+			}
+			Self::Copying { name, method, from } => {
+				write!(
+					f,
+					"{:?}: copying {}-compressed from {:?}",
+					name, method, from
+				)
+			}
+			Self::Aligning { name, bytes } => {
+				write!(f, "{:?}: starting file {}-byte aligned", name, bytes)
+			}
+			Self::Padding { name, bytes } => write!(f, "{:?}: via {}-byte pad", name, bytes),
+			Self::Recompressing { name, method } => {
+				write!(f, "{:?}: starting file {}-recompressed", name, method)
+			}
+			Self::Clamping {
+				name,
+				method,
+				level,
+			} => write!(f, "{:?}: clamping {} to level {}", name, method, level),
+			Self::Selecting {
+				name,
+				method,
+				reason,
+			} => write!(f, "{:?}: selecting {} as {}", name, method, reason),
+			Self::Stacking { name, files } => write!(f, "{:?}: stacking {} files", name, files),
+			Self::StackingFrom { name, from } => {
+				write!(f, "{:?}: stacking from {:?}", name, from)
+			}
+			Self::Merging { name, from } => write!(f, "{:?}: merging from {:?}", name, from),
+			Self::Excluding { name, from, bytes } => {
+				write!(f, "{:?}: excluding {} bytes from {:?}", name, bytes, from)
+			}
+			Self::Written { name, bytes } => write!(f, "{:?}: written {} bytes", name, bytes),
+			Self::AddingDirectory { name } => write!(f, "{:?}: adding directory", name),
+			Self::Finishing { path, volume } => match volume {
+				Some(volume) => write!(f, "{:?}: finishing as {:?}", path, volume),
+				None => write!(f, "{:?}: finishing", path),
+			},
+			Self::TotalPadding { path, bytes } => {
+				write!(f, "{:?}: via {}-byte pad in total", path, bytes)
+			}
+			Self::Duplicate { name, of, dropped } => {
+				let dropping = if *dropped { "dropping " } else { "" };
+				write!(f, "{:?}: {}duplicate of {:?}", name, dropping, of)
+			}
+			Self::TotalDuplicates {
+				path,
+				files,
+				bytes,
+				dropped,
+			} => {
+				let plural = if *files == 1 { "" } else { "s" };
+				let verb = if *dropped { "dropped" } else { "droppable" };
+				write!(
+					f,
+					"{:?}: {} duplicate file{} of {} bytes {}",
+					path, files, plural, bytes, verb
+				)
+			}
+			Self::Changed { name, change } => write!(f, "{:?}: {}", name, change),
+			Self::TotalChanges {
+				path,
+				against,
+				added,
+				removed,
+				modified,
+				unchanged,
+			} => write!(
+				f,
+				"{:?}: {} added, {} removed, {} modified, and {} unchanged files compared to {:?}",
+				path, added, removed, modified, unchanged, against
+			),
+			Self::Compressed {
+				name,
+				method,
+				from,
+				requested,
+			} => {
+				let not = if *requested { "" } else { "not " };
+				write!(f, "{:?}: {}{}-compressed in {:?}", name, not, method, from)
+			}
+			Self::Aligned {
+				name,
+				bytes,
+				from,
+				requested,
+			} => {
+				let not = if *requested { "" } else { "not " };
+				write!(f, "{:?}: {}{}-byte aligned in {:?}", name, not, bytes, from)
+			}
+			Self::Caching {
+				name,
+				method,
+				reused: true,
+			} => write!(f, "{:?}: reusing cached {}", name, method),
+			Self::Caching {
+				name,
+				method,
+				reused: false,
+			} => write!(f, "{:?}: caching {}", name, method),
+			Self::Missing {
+				name,
+				from,
+				zeroed: true,
+			} => write!(f, "{:?}: stacking zeros as missing in {:?}", name, from),
+			Self::Missing {
+				name,
+				from,
+				zeroed: false,
+			} => write!(f, "{:?}: merging as missing in {:?}", name, from),
+			Self::Extracting { name, to } => write!(f, "{:?}: extracting to {:?}", name, to),
+			Self::Reported { name, method, from } => {
+				write!(f, "{:?}: {}-compressed in {:?}", name, method, from)
+			}
+			Self::Numbering { name, to, from } => {
+				write!(f, "{:?}: numbering as {:?} from {:?}", name, to, from)
+			}
+			Self::Addressing { name, to } => write!(f, "{:?}: addressing as {:?}", name, to),
+			Self::Corrupted { name, from, error } => {
+				write!(f, "{:?}: corrupted in {:?} as {}", name, from, error)
+			}
+			Self::Timing {
+				path,
+				phase,
+				from,
+				seconds,
+				bytes,
+			} => {
+				match *phase {
+					"total" => write!(f, "{:?}: {} bytes", path, bytes)?,
+					_ if *bytes > 0 => write!(f, "{:?}: {} {} bytes", path, phase, bytes)?,
+					_ => write!(f, "{:?}: {}", path, phase)?,
+				}
+				match from {
+					Some(from) => write!(f, " from {:?} in {:.3} s", from, seconds)?,
+					None => write!(f, " in {:.3} s in total", seconds)?,
+				}
+				if *bytes > 0 && *seconds > 0.0 {
+					write!(f, " at {:.1} MB/s", *bytes as f64 / seconds / 1e6)?;
+				}
+				Ok(())
+			}
+			Self::Checked => write!(f, "Compressed and aligned as requested"),
+		}
+	}
+}
+
+fn check_level(
+	algorithm: CompressionMethod,
+	level: Option<i32>,
+	window_log: Option<u32>,
+	header: &[u8],
+) -> Option<bool> {
+	match algorithm {
+		CompressionMethod::Bzip2 => match header {
+			[b'B', b'Z', b'h', block @ b'1'..=b'9', ..] => Some(i32::from(block - b'0') == level?),
+			_ => None,
+		},
+		CompressionMethod::Zstd => match header {
+			[0x28, 0xb5, 0x2f, 0xfd, descriptor, window, ..] if descriptor & 0x20 == 0 => {
+				let actual = u32::from(window >> 3) + 10;
+				let mantissa = window & 0x07;
+				let expected = match window_log {
+					Some(window_log) => window_log,
+					None => {
+						let level = usize::try_from(level?).ok()?.checked_sub(1)?;
+						u32::from(*ZSTD_WINDOW_LOGS.get(level)?)
+					}
+				};
+				Some(mantissa == 0 && actual == expected)
+			}
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+// Default window logs of zstd levels 1 to 22 for unknown content sizes.
+const ZSTD_WINDOW_LOGS: [u8; 22] = [
+	19, 20, 21, 21, 21, 21, 21, 21, 22, 22, 22, 22, 22, 22, 22, 22, 23, 23, 23, 25, 26, 27,
+];
+
+// Names the method with the level or range of levels inferred from the header of the data.
+fn infer_method(algorithm: CompressionMethod, header: &[u8]) -> String {
+	let levels = match (algorithm, header) {
+		(CompressionMethod::Bzip2, [b'B', b'Z', b'h', block @ b'1'..=b'9', ..]) => {
+			let level = block - b'0';
+			Some((level, level))
+		}
+		(CompressionMethod::Zstd, [0x28, 0xb5, 0x2f, 0xfd, descriptor, window, ..])
+			if descriptor & 0x20 == 0 && window & 0x07 == 0 =>
+		{
+			let window_log = (window >> 3) + 10;
+			let levels = ZSTD_WINDOW_LOGS
+				.iter()
+				.zip(1..)
+				.filter(|&(&other, _level)| other == window_log)
+				.map(|(_window_log, level)| level);
+			levels.clone().min().zip(levels.max())
+		}
+		_ => None,
+	};
+	let algorithm = method_name(algorithm, None);
+	match levels {
+		Some((min, max)) if min == max => format!("{}:{}", algorithm, min),
+		Some((min, max)) => format!("{}:{}-{}", algorithm, min, max),
+		None => algorithm,
+	}
+}
+
+// Levels of the algorithm accepted by --recompress, --aligned-compressed, and --max-level.
+fn level_range(algorithm: CompressionMethod) -> Option<RangeInclusive<i32>> {
+	match algorithm {
+		CompressionMethod::Deflated => Some(0..=9),
+		CompressionMethod::Bzip2 => Some(1..=9),
+		CompressionMethod::Zstd => Some(1..=21),
+		_ => None,
+	}
+}
+
+// Parses the level of the algorithm given in the method or codec.
+fn parse_level(algorithm: CompressionMethod, level: &str, method: &str) -> Result<i32> {
+	let level = level.parse::<i32>()?;
+	if level_range(algorithm).is_some_and(|range| range.contains(&level)) {
+		Ok(level)
+	} else {
+		Err(eyre!("Invalid level in {:?}", method))
+	}
+}
+
+fn clamp_level(algorithm: CompressionMethod, level: i32, max_level: i32) -> Option<i32> {
+	let range = level_range(algorithm)?;
+	let max_level = max_level.clamp(*range.start(), *range.end());
+	(level > max_level).then_some(max_level)
+}
+
+fn method_name(algorithm: CompressionMethod, level: Option<i32>) -> String {
+	let algorithm = algorithm.to_string().to_lowercase();
+	level.map_or(algorithm.clone(), |level| {
+		format!("{}:{}", algorithm, level)
+	})
+}
+
+fn parse_size(size: &str) -> Result<u64, String> {
+	let digits = size
+		.find(|c: char| !c.is_ascii_digit())
+		.unwrap_or(size.len());
+	let (number, suffix) = size.split_at(digits);
+	let shift = match suffix {
+		"" | "B" => 0,
+		"KiB" => 10,
+		"MiB" => 20,
+		"GiB" => 30,
+		"TiB" => 40,
+		_ => return Err(format!("Invalid suffix {:?}", suffix)),
+	};
+	number
+		.parse::<u64>()
+		.map_err(|err| err.to_string())?
+		.checked_mul(1 << shift)
+		.ok_or_else(|| "Size too large".to_string())
+}
+
+fn parse_glob_value<F, T>(values: &[String], parse: F) -> Result<Vec<(Glob, Option<T>)>>
+where
+	F: Fn(&str) -> Result<T>,
+{
+	parse_glob_value_at(values, |value| value.rfind('='), parse)
+}
+
+// Splits glob-value pairs at the given position of `=` instead of its last occurrence, like at
+// its first one for values which may contain `=` themselves.
+fn parse_glob_value_at<S, F, T>(
+	values: &[String],
+	split: S,
+	parse: F,
+) -> Result<Vec<(Glob, Option<T>)>>
+where
+	S: Fn(&str) -> Option<usize>,
+	F: Fn(&str) -> Result<T>,
+{
+	values
+		.iter()
+		.map(|value| {
+			let (left, right) = split(value)
+				.map(|mid| value.split_at(mid))
+				.map(|(left, right)| (left, &right[1..]))
+				.unwrap_or(("*", value));
+			Glob::new(left)
+				.wrap_err_with(|| format!("Invalid glob pattern {:?}", left))
+				.and_then(|left| {
+					if right.is_empty() {
+						Ok(None)
+					} else {
+						parse(right).map(Some)
+					}
+					.map(|right| (left, right))
+				})
+		})
+		.collect()
+}
+
+type InputArchive = Input<BufReader<fs::File>, BufReader<fs::File>>;
+
+// Options of reading input directories and files of --merge.
+#[derive(Clone, Copy)]
+struct DirOptions<'a> {
+	follow_links: bool,
+	base: Option<&'a Path>,
+	modes: &'a [(Glob, Option<u32>)],
+	times: &'a [(Glob, Option<DateTime>)],
+}
+
+impl DirOptions<'_> {
+	fn file<R: Read>(
+		&self,
+		path: &Path,
+		name: String,
+		metadata: Metadata,
+		options: GlobOptions,
+	) -> DirFile<R> {
+		let mut file = DirFile::new(path, name, metadata);
+		file.mode = match_glob_value(self.modes, Path::new(&file.name), options);
+		file.modified = match_glob_value(self.times, Path::new(&file.name), options);
+		file
+	}
+}
+
+struct Inputs<'a> {
+	paths: Vec<PathBuf>,
+	merge: &'a [(Glob, Option<String>)],
+	options: GlobOptions,
+	dir: DirOptions<'a>,
+	open: IndexMap<usize, InputArchive>,
+	max_open: usize,
+}
+
+impl<'a> Inputs<'a> {
+	fn new(
+		merge: &'a [(Glob, Option<String>)],
+		options: GlobOptions,
+		dir: DirOptions<'a>,
+		max_open: usize,
+	) -> Self {
+		Self {
+			paths: Vec::new(),
+			merge,
+			options,
+			dir,
+			open: IndexMap::new(),
+			max_open,
+		}
+	}
+	fn push(&mut self, path: PathBuf, zip: InputArchive) {
+		self.paths.push(path);
+		self.insert(self.paths.len() - 1, zip);
+	}
+	fn insert(&mut self, input: usize, zip: InputArchive) -> &mut InputArchive {
+		// Closes the least recently used input archive.
+		if self.open.len() >= self.max_open {
+			self.open.shift_remove_index(0);
+		}
+		let (index, _zip) = self.open.insert_full(input, zip);
+		&mut self.open[index]
+	}
+	fn get(&mut self, input: usize) -> Result<&mut InputArchive> {
+		let zip = match self.open.shift_remove(&input) {
+			Some(zip) => zip,
+			None => self.open(&self.paths[input])?,
+		};
+		Ok(self.insert(input, zip))
+	}
+	fn open(&self, path: &Path) -> Result<InputArchive> {
+		Input::new(path, self.merge, self.options, self.dir)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TarCodec {
+	Plain,
+	Gzip,
+	Zstd,
+}
+
+fn tar_suffix(path: &Path) -> Option<TarCodec> {
+	let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+	if name.ends_with(".tar") {
+		Some(TarCodec::Plain)
+	} else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+		Some(TarCodec::Gzip)
+	} else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+		Some(TarCodec::Zstd)
+	} else {
+		None
+	}
+}
+
+// Splits --rename at the first `=` not escaped as `\=` into the unescaped regular expression and
+// the replacement, which may contain `=` as is.
+fn split_rename(rename: &str) -> Option<(String, &str)> {
+	let mut regex = String::new();
+	let mut chars = rename.char_indices();
+	while let Some((mid, char)) = chars.next() {
+		match char {
+			'=' => return Some((regex, &rename[mid + 1..])),
+			'\\' => {
+				let next = chars.next().map(|(_mid, char)| char);
+				if next != Some('=') {
+					regex.push(char);
+				}
+				regex.extend(next);
+			}
+			char => regex.push(char),
+		}
+	}
+	None
+}
+
+fn normalize_name(name: &str, is_dir: bool) -> Option<String> {
+	let mut normalized = name
+		.split(['/', '\\'])
+		.filter(|component| !component.is_empty() && *component != ".")
+		.collect::<Vec<_>>()
+		.join("/");
+	if normalized.is_empty() {
+		return None;
+	}
+	if is_dir {
+		normalized.push('/');
+	}
+	Some(normalized)
+}
+
+// Strips leading components like tar does unless none remain.
+fn strip_name(name: &str, count: usize) -> Option<&str> {
+	let mut stripped = name.trim_start_matches('/');
+	for _ in 0..count {
+		let (_component, rest) = stripped.split_once('/')?;
+		stripped = rest.trim_start_matches('/');
+	}
+	(!stripped.is_empty()).then_some(stripped)
+}
+
+// Keys directories by their normalized names regardless of case, keeping the first name seen.
+fn dedup_dir(dirs: &mut HashMap<String, PathBuf>, name: &Path) -> PathBuf {
+	let name = name.to_string_lossy();
+	let normalized = normalize_name(&name, true).unwrap_or_else(|| name.into_owned());
+	dirs.entry(normalized.to_lowercase())
+		.or_insert_with(|| PathBuf::from(normalized))
+		.clone()
+}
+
+// Inserts the number before the first extension of the file name as in data.1.npy.
+fn number_name(name: &Path, number: usize) -> PathBuf {
+	let file_name = name.file_name().and_then(OsStr::to_str).unwrap_or_default();
+	let dot = file_name
+		.char_indices()
+		.skip(1)
+		.find(|&(_index, char)| char == '.');
+	let (stem, extension) = match dot {
+		Some((dot, _char)) => file_name.split_at(dot),
+		None => (file_name, ""),
+	};
+	name.with_file_name(format!("{}.{}{}", stem, number, extension))
+}
+
+fn merge_name(template: &str, name: &Path) -> String {
+	let file_name = name.file_name().and_then(OsStr::to_str).unwrap_or_default();
+	if template.ends_with('/') {
+		format!("{}{}", template, file_name)
+	} else {
+		template.replace("{}", file_name)
+	}
+}
+
+// Glob of names optionally qualified by a glob of input ZIP archives as in archive:glob.
+struct Glob {
+	from: Option<Pattern>,
+	name: Pattern,
+}
+
+impl Glob {
+	fn new(glob: &str) -> Result<Self, glob::PatternError> {
+		match qualifier_end(glob) {
+			Some(mid) => Ok(Self {
+				from: Some(Pattern::new(&glob[..mid])?),
+				name: Pattern::new(&glob[mid + 1..])?,
+			}),
+			None => Ok(Self {
+				from: None,
+				name: Pattern::new(glob)?,
+			}),
+		}
+	}
+}
+
+// Extensions of input archives a qualifier must end with.
+const QUALIFIER_EXTENSIONS: [&str; 7] = [
+	".zip", ".npz", ".tar", ".tar.gz", ".tgz", ".tar.zst", ".tzst",
+];
+
+// Finds the first `:` outside of character classes preceded by a glob ending in an extension of
+// input archives, so names like `b:c.txt` and drive letters like `C:` are not mistaken for
+// qualifiers, whereas `[:]` matches a literal `:` after such an extension.
+fn qualifier_end(glob: &str) -> Option<usize> {
+	let mut class = false;
+	glob.char_indices().find_map(|(mid, char)| {
+		match char {
+			'[' if !class => class = true,
+			']' if class => class = false,
+			':' if !class => {
+				let from = glob[..mid].to_ascii_lowercase();
+				if QUALIFIER_EXTENSIONS
+					.iter()
+					.any(|extension| from.ends_with(extension))
+				{
+					return Some(mid);
+				}
+			}
+			_ => {}
+		}
+		None
+	})
+}
+
+#[derive(Clone, Copy, Debug)]
+struct GlobOptions {
+	options: MatchOptions,
+	basename: bool,
+}
+
+impl GlobOptions {
+	fn matches(&self, glob: &Glob, path: &Path, from: Option<&Path>) -> bool {
+		let from = match (&glob.from, from) {
+			(Some(glob), Some(from)) => self.matches_path(glob, from),
+			(Some(_glob), None) => false,
+			(None, _from) => true,
+		};
+		from && self.matches_path(&glob.name, path)
+	}
+	fn matches_path(&self, glob: &Pattern, path: &Path) -> bool {
+		let path = match path.file_name() {
+			Some(name) if self.basename && !glob.as_str().contains('/') => Path::new(name),
+			_ => path,
+		};
+		glob.matches_path_with(path, self.options)
+	}
+}
+
+fn match_glob_value<T: Clone, P: AsRef<Path>>(
+	values: &[(Glob, Option<T>)],
+	name: P,
+	options: GlobOptions,
+) -> Option<T> {
+	match_glob_values(values, &[name.as_ref()], None, options)
+}
+
+// Matches qualified globs as well if the name is of a file from the given input ZIP archive.
+fn match_input_glob_value<T: Clone>(
+	values: &[(Glob, Option<T>)],
+	name: &Path,
+	from: Option<&Path>,
+	options: GlobOptions,
+) -> Option<T> {
+	match_glob_values(values, &[name], from, options)
+}
+
+fn match_glob_values<T: Clone>(
+	values: &[(Glob, Option<T>)],
+	names: &[&Path],
+	from: Option<&Path>,
+	options: GlobOptions,
+) -> Option<T> {
+	values
+		.iter()
+		.rev()
+		.find_map(|(glob, value)| {
+			if names.iter().any(|name| options.matches(glob, name, from)) {
+				Some(value)
+			} else {
+				None
+			}
+		})
+		.cloned()
+		.flatten()
+}
+
+enum Input<D: Read, Z: Read + Seek> {
+	Dir(DirArchive<D>),
+	Zip(ZipArchive<Z>),
+	Tar(TarArchive<Z>),
+}
+
+struct DirArchive<D: Read> {
+	files: IndexMap<usize, DirFile<D>>,
+	open: fn(&Path) -> io::Result<D>,
+	opened: Option<usize>,
+}
+
+impl DirArchive<BufReader<fs::File>> {
+	fn new(files: IndexMap<usize, DirFile<BufReader<fs::File>>>) -> Self {
+		Self {
+			files,
+			open: |path| OpenOptions::new().read(true).open(path).map(BufReader::new),
+			opened: None,
+		}
+	}
+}
+
+impl<D: Read> DirArchive<D> {
+	fn len(&self) -> usize {
+		self.files.len()
+	}
+	fn by_index(&mut self, index: usize) -> Result<&mut DirFile<D>> {
+		// Opens one file at a time, from its start whenever it is read again.
+		if let Some(file) = self
+			.opened
+			.take()
+			.and_then(|index| self.files.get_mut(&index))
+		{
+			file.reader = None;
+		}
+		let file = self
+			.files
+			.get_mut(&index)
+			.ok_or_else(|| eyre!("Invalid file index {}", index))?;
+		if !file.metadata.is_dir() {
+			let reader = (self.open)(&file.path)
+				.wrap_err_with(|| format!("Cannot open input file {:?}", file.path))?;
+			file.reader = Some(reader);
+			self.opened = Some(index);
+		}
+		Ok(file)
+	}
+}
+
+struct DirFile<R: Read> {
+	name: String,
+	path: PathBuf,
+	metadata: Metadata,
+	mode: Option<u32>,
+	modified: Option<DateTime>,
+	reader: Option<R>,
+}
+
+impl<R: Read> DirFile<R> {
+	fn new(path: &Path, name: String, metadata: Metadata) -> Self {
+		DirFile {
+			name,
+			path: path.to_path_buf(),
+			metadata,
+			mode: None,
+			modified: None,
+			reader: None,
+		}
+	}
+}
+
+struct TarArchive<R: Read + Seek> {
+	reader: R,
+	files: Vec<TarEntry>,
+}
+
+struct TarEntry {
+	name: String,
+	size: u64,
+	mtime: u64,
+	mode: u32,
+	owner: Option<(u32, u32)>,
+	is_dir: bool,
+	data_start: u64,
+}
+
+impl<R: Read + Seek> TarArchive<R> {
+	fn new(mut reader: R) -> io::Result<Self> {
+		let mut files = Vec::new();
+		for entry in tar::Archive::new(&mut reader).entries_with_seek()? {
+			let entry = entry?;
+			let header = entry.header();
+			let is_dir = match header.entry_type() {
+				tar::EntryType::Regular | tar::EntryType::Continuous => false,
+				tar::EntryType::Directory => true,
+				_ => continue,
+			};
+			let path = entry.path()?;
+			// Rejects absolute paths and parent directories like `tar::Entry::unpack_in()`.
+			let name = path
+				.components()
+				.filter(|component| component != &Component::CurDir)
+				.map(|component| match component {
+					Component::Normal(component) => component.to_str(),
+					_ => None,
+				})
+				.collect::<Option<Vec<_>>>()
+				.ok_or_else(|| {
+					io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("Invalid file name {:?}", path),
+					)
+				})?
+				.join("/");
+			if name.is_empty() {
+				continue;
+			}
+			files.push(TarEntry {
+				name,
+				size: entry.size(),
+				mtime: header.mtime()?,
+				mode: header.mode()?,
+				owner: header
+					.uid()
+					.ok()
+					.and_then(|uid| u32::try_from(uid).ok())
+					.zip(header.gid().ok().and_then(|gid| u32::try_from(gid).ok())),
+				is_dir,
+				data_start: entry.raw_file_position(),
+			});
+		}
+		Ok(Self { reader, files })
+	}
+	fn len(&self) -> usize {
+		self.files.len()
+	}
+	fn by_index(&mut self, index: usize) -> Result<TarFile<'_>> {
+		let entry = self
+			.files
+			.get(index)
+			.ok_or_else(|| eyre!("Invalid file index {}", index))?;
+		self.reader.seek(io::SeekFrom::Start(entry.data_start))?;
+		let reader: &mut dyn Read = &mut self.reader;
+		Ok(TarFile {
+			entry,
+			reader: reader.take(entry.size),
+		})
+	}
+}
+
+struct TarFile<'a> {
+	entry: &'a TarEntry,
+	reader: io::Take<&'a mut dyn Read>,
+}
+
+#[allow(clippy::enum_variant_names)]
+enum File<'a, R: Read> {
+	DirFile(&'a mut DirFile<R>),
+	ZipFile(ZipFile<'a>),
+	TarFile(TarFile<'a>),
+}
+
+impl<'a, R: Read> File<'a, R> {
+	fn name(&self) -> &Path {
+		match self {
+			Self::DirFile(file) => Path::new(&file.name),
+			Self::ZipFile(file) => Path::new(file.name()),
+			Self::TarFile(file) => Path::new(&file.entry.name),
+		}
+	}
+	// Decodes the name as UTF-8 regardless of the language encoding flag.
+	fn utf8_name(&self) -> Option<&Path> {
+		match self {
+			Self::ZipFile(file) => std::str::from_utf8(file.name_raw()).ok().map(Path::new),
+			Self::DirFile(_) | Self::TarFile(_) => Some(self.name()),
+		}
+	}
+	fn compression(&self) -> CompressionMethod {
+		match self {
+			Self::DirFile(_file) => CompressionMethod::Stored,
+			Self::ZipFile(file) => file.compression(),
+			Self::TarFile(_file) => CompressionMethod::Stored,
+		}
+	}
+	fn last_modified(&self) -> DateTime {
+		match self {
+			Self::DirFile(file) => file.modified.unwrap_or_else(|| {
+				file.metadata
+					.modified()
+					.ok()
+					.map(OffsetDateTime::from)
+					.and_then(|mtime| DateTime::try_from(mtime).ok())
+					.unwrap_or_default()
+			}),
+			Self::ZipFile(file) => file.last_modified(),
+			Self::TarFile(file) => i64::try_from(file.entry.mtime)
+				.ok()
+				.and_then(|mtime| OffsetDateTime::from_unix_timestamp(mtime).ok())
+				.and_then(|mtime| DateTime::try_from(mtime).ok())
+				.unwrap_or_default(),
+		}
+	}
+	fn is_dir(&self) -> bool {
+		match self {
+			Self::DirFile(file) => file.metadata.is_dir(),
+			Self::ZipFile(file) => file.is_dir(),
+			Self::TarFile(file) => file.entry.is_dir,
+		}
+	}
+	fn extra_data(&self) -> &[u8] {
+		match self {
+			Self::DirFile(_file) => &[],
+			Self::ZipFile(file) => file.extra_data(),
+			Self::TarFile(_file) => &[],
+		}
+	}
+	fn unix_mode(&self) -> Option<u32> {
+		match self {
+			#[cfg(unix)]
+			Self::DirFile(file) => {
+				use std::os::unix::fs::PermissionsExt;
+				Some(
+					file.mode
+						.unwrap_or(file.metadata.permissions().mode() & 0o7777),
+				)
+			}
+			#[cfg(not(unix))]
+			Self::DirFile(file) => file.mode,
+			Self::ZipFile(file) => file.unix_mode(),
+			Self::TarFile(file) => Some(file.entry.mode),
+		}
+	}
+	fn owner(&self) -> Option<(u32, u32)> {
+		match self {
+			#[cfg(unix)]
+			Self::DirFile(file) => {
+				use std::os::unix::fs::MetadataExt;
+				Some((file.metadata.uid(), file.metadata.gid()))
+			}
+			#[cfg(not(unix))]
+			Self::DirFile(_file) => None,
+			Self::ZipFile(file) => unix_owner(file.extra_data()),
+			Self::TarFile(file) => file.entry.owner,
+		}
+	}
+	fn size(&self) -> u64 {
+		match self {
+			Self::DirFile(file) if file.metadata.is_dir() => 0,
+			Self::DirFile(file) => file.metadata.len(),
+			Self::ZipFile(file) => file.size(),
+			Self::TarFile(file) => file.entry.size,
+		}
+	}
+	fn compressed_size(&self) -> u64 {
+		match self {
+			Self::DirFile(file) if file.metadata.is_dir() => 0,
+			Self::DirFile(file) => file.metadata.len(),
+			Self::ZipFile(file) => file.compressed_size(),
+			Self::TarFile(file) => file.entry.size,
+		}
+	}
+	fn crc32(&self) -> Option<u32> {
+		match self {
+			Self::DirFile(_file) => None,
+			Self::ZipFile(file) => Some(file.crc32()),
+			Self::TarFile(_file) => None,
+		}
+	}
+	fn data_start(&self) -> Option<u64> {
+		match self {
+			Self::DirFile(_file) => None,
+			Self::ZipFile(file) => Some(file.data_start()),
+			Self::TarFile(_file) => None,
+		}
+	}
+}
+
+impl<'a, R: Read> Read for File<'a, R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match self {
+			Self::DirFile(file) => {
+				if let Some(file) = &mut file.reader {
+					file.read(buf)
+				} else {
+					Err(io::Error::other("Not readable"))
+				}
+			}
+			Self::ZipFile(file) => file.read(buf),
+			Self::TarFile(file) => file.reader.read(buf),
+		}
+	}
+}
+
+impl<D: Read, Z: Read + Seek> Input<D, Z> {
+	fn len(&self) -> usize {
+		match self {
+			Self::Dir(dir) => dir.len(),
+			Self::Zip(zip) => zip.len(),
+			Self::Tar(tar) => tar.len(),
+		}
+	}
+	fn by_index(&mut self, index: usize) -> Result<File<'_, D>> {
+		match self {
+			Self::Dir(dir) => dir.by_index(index).map(File::DirFile),
+			Self::Zip(zip) => zip.by_index(index).map(File::ZipFile).map_err(From::from),
+			Self::Tar(tar) => tar.by_index(index).map(File::TarFile),
+		}
+	}
+	fn by_index_raw(&mut self, index: usize) -> Option<ZipFile<'_>> {
+		match self {
+			Self::Dir(_) | Self::Tar(_) => None,
+			Self::Zip(zip) => zip.by_index_raw(index).ok(),
+		}
+	}
+}
+
+impl Input<BufReader<fs::File>, BufReader<fs::File>> {
+	fn new<P: AsRef<Path>>(
+		path: P,
+		merge: &[(Glob, Option<String>)],
+		options: GlobOptions,
+		dir: DirOptions,
+	) -> Result<Self> {
+		let path = path.as_ref();
+		let metadata =
+			fs::metadata(path).wrap_err_with(|| format!("Cannot get metadata of {:?}", path))?;
+		if let Some(name) = match_glob_value(merge, path, options) {
+			let mut files = IndexMap::new();
+			let file = dir.file(path, merge_name(&name, path), metadata, options);
+			files.insert(0, file);
+			Ok(Self::Dir(DirArchive::new(files)))
+		} else {
+			if metadata.is_dir() {
+				let mut files = IndexMap::new();
+				let entries = WalkDir::new(path)
+					.follow_links(dir.follow_links)
+					.sort_by(|a, b| a.file_name().cmp(b.file_name()))
+					.into_iter();
+				for entry in entries {
+					let entry = entry.wrap_err_with(|| format!("Cannot traverse {:?}", path))?;
+					if entry.path_is_symlink() && entry.depth() > 0 && !dir.follow_links {
+						debug!("Skipping symbolic link {:?}", entry.path());
+						continue;
+					}
+					let name = match dir.base {
+						Some(base) => entry.path().strip_prefix(base).wrap_err_with(|| {
+							format!("Cannot strip base {:?} from {:?}", base, entry.path())
+						})?,
+						None => entry.path(),
+					};
+					if name.as_os_str().is_empty() {
+						continue;
+					}
+					let name = name
+						.to_str()
+						.ok_or_else(|| eyre!("Invalid file name {:?}", entry.path()))?
+						.to_string();
+					let metadata = entry
+						.metadata()
+						.wrap_err_with(|| format!("Cannot get metadata of {:?}", name))?;
+					let file = dir.file(entry.path(), name, metadata, options);
+					files.insert(files.len(), file);
+				}
+				Ok(Self::Dir(DirArchive::new(files)))
+			} else if let Some(codec) = tar_suffix(path) {
+				let mut reader = OpenOptions::new()
+					.read(true)
+					.open(path)
+					.map(BufReader::new)
+					.wrap_err_with(|| format!("Cannot open input TAR archive {:?}", path))?;
+				if codec != TarCodec::Plain {
+					let mut file = tempfile::tempfile()
+						.map(BufWriter::new)
+						.wrap_err("Cannot create temporary file")?;
+					match codec {
+						TarCodec::Gzip => copy(&mut GzDecoder::new(reader), &mut file),
+						TarCodec::Zstd => zstd::stream::copy_decode(reader, &mut file).map(|()| 0),
+						TarCodec::Plain => unreachable!(),
+					}
+					.wrap_err_with(|| format!("Cannot decompress input TAR archive {:?}", path))?;
+					let mut file = file
+						.into_inner()
+						.map_err(io::IntoInnerError::into_error)
+						.wrap_err("Cannot write temporary file")?;
+					file.rewind().wrap_err("Cannot read temporary file")?;
+					reader = BufReader::new(file);
+				}
+				TarArchive::new(reader)
+					.map(Self::Tar)
+					.wrap_err_with(|| format!("Cannot read input TAR archive {:?}", path))
+			} else {
+				OpenOptions::new()
+					.read(true)
+					.open(path)
+					.wrap_err_with(|| format!("Cannot open input ZIP archive {:?}", path))
+					.map(BufReader::new)
+					.and_then(|zip| {
+						ZipArchive::new(zip)
+							.wrap_err_with(|| format!("Cannot read input ZIP archive {:?}", path))
+					})
+					.map(Self::Zip)
+			}
+		}
+	}
+}
+
+struct Output<W: Write + Seek> {
+	writer: W,
+	position: u64,
+	length: Rc<Cell<u64>>,
+}
+
+impl<W: Write + Seek> Write for Output<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let length = self.writer.write(buf)?;
+		self.position += length as u64;
+		self.length.set(self.length.get().max(self.position));
+		Ok(length)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+impl<W: Write + Seek> Seek for Output<W> {
+	fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+		self.position = self.writer.seek(pos)?;
+		Ok(self.position)
+	}
+}
+
+type OutputZip = ZipWriter<Output<BufWriter<fs::File>>>;
+
+// Writes a sibling temporary file removed unless renamed to the output path when finished.
+fn create_output(
+	path: &Path,
+	tmpdir: Option<&Path>,
+	force: bool,
+	stub: Option<&[u8]>,
+) -> Result<(OutputZip, Rc<Cell<u64>>, TempPath)> {
+	if !force && path.exists() {
+		return Err(eyre!(
+			"Cannot create existing output ZIP archive {:?}",
+			path
+		));
+	}
+	let parent = path
+		.parent()
+		.filter(|dir| !dir.as_os_str().is_empty())
+		.unwrap_or_else(|| Path::new("."));
+	let dir = tmpdir.unwrap_or(parent);
+	#[cfg(unix)]
+	if let Some(tmpdir) = tmpdir {
+		use std::os::unix::fs::MetadataExt;
+		let device = |dir: &Path| {
+			fs::metadata(dir)
+				.map(|metadata| metadata.dev())
+				.wrap_err_with(|| format!("Cannot get metadata of {:?}", dir))
+		};
+		if device(tmpdir)? != device(parent)? {
+			return Err(eyre!(
+				"Cannot rename from temporary directory {:?} on another file system than {:?}",
+				tmpdir,
+				parent
+			));
+		}
+	}
+	let mut prefix = path.file_name().unwrap_or_default().to_owned();
+	prefix.push(".tmp-");
+	let length = Rc::new(Cell::new(0));
+	tempfile::Builder::new()
+		.prefix(&prefix)
+		.tempfile_in(dir)
+		.map(NamedTempFile::into_parts)
+		.and_then(|(file, temporary)| {
+			let mut writer = Output {
+				writer: BufWriter::new(file),
+				position: 0,
+				length: length.clone(),
+			};
+			// Offsets written by the ZIP writer are absolute, accounting for the stub.
+			writer.write_all(stub.unwrap_or_default())?;
+			Ok((ZipWriter::new(writer), length, temporary))
+		})
+		.wrap_err_with(|| format!("Cannot create output ZIP archive {:?}", path))
+}
+
+fn finish_output(
+	path: &Path,
+	zip: &mut OutputZip,
+	temporary: TempPath,
+	force: bool,
+	verified: Option<&[(PathBuf, Vec<usize>)]>,
+	trailer: &[u8],
+) -> Result<()> {
+	zip.finish()
+		.and_then(|mut zip| {
+			zip.write_all(trailer)
+				.and_then(|()| zip.flush())
+				.map_err(From::from)
+		})
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	if let Some(shapes) = verified {
+		verify(&temporary, shapes)
+			.wrap_err_with(|| format!("Cannot verify output ZIP archive {:?}", path))?;
+	}
+	if force {
+		temporary.persist(path)
+	} else {
+		temporary.persist_noclobber(path)
+	}
+	.wrap_err_with(|| format!("Cannot rename output ZIP archive to {:?}", path))
+}
+
+// Reads all files of the written ZIP archive and the shapes of its stacked arrays.
+fn verify(path: &Path, shapes: &[(PathBuf, Vec<usize>)]) -> Result<()> {
+	let mut zip = fs::File::open(path)
+		.map(BufReader::new)
+		.map_err(ZipError::from)
+		.and_then(ZipArchive::new)?;
+	let shapes = shapes
+		.iter()
+		.map(|(name, shape)| (name.as_path(), shape.as_slice()))
+		.collect::<HashMap<_, _>>();
+	let mut stacked = 0;
+	for index in 0..zip.len() {
+		let mut file = zip.by_index(index)?;
+		let name = PathBuf::from(file.name());
+		let Some(&shape) = shapes.get(name.as_path()) else {
+			copy(&mut file, &mut io::sink()).wrap_err_with(|| format!("Cannot read {:?}", name))?;
+			continue;
+		};
+		let mut npy = Vec::new();
+		if Format::new(&name) == Some(Format::NpyGz) {
+			GzDecoder::new(file).read_to_end(&mut npy)
+		} else {
+			file.read_to_end(&mut npy)
+		}
+		.wrap_err_with(|| format!("Cannot read {:?}", name))?;
+		let written = npy_shape(&npy);
+		if written.as_deref() != Some(shape) {
+			return Err(eyre!(
+				"Mismatching shape {:?} of {:?} instead of {:?}",
+				written,
+				name,
+				shape
+			));
+		}
+		stacked += 1;
+	}
+	if stacked < shapes.len() {
+		return Err(eyre!("Missing {} stacked arrays", shapes.len() - stacked));
+	}
+	Ok(())
+}
+
+// Digests the uncompressed files of the ZIP archive by their names.
+fn digest_files(path: &Path) -> Result<IndexMap<PathBuf, [u8; 32]>> {
+	let mut zip = fs::File::open(path)
+		.map(BufReader::new)
+		.map_err(ZipError::from)
+		.and_then(ZipArchive::new)?;
+	let mut digests = IndexMap::new();
+	for index in 0..zip.len() {
+		let mut file = zip.by_index(index)?;
+		let name = PathBuf::from(file.name());
+		let mut hasher = Sha256::new();
+		copy(&mut file, &mut hasher).wrap_err_with(|| format!("Cannot digest {:?}", name))?;
+		digests.insert(name, hasher.finalize().into());
+	}
+	Ok(digests)
+}
+
+fn extra_fields(mut extra: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+	std::iter::from_fn(move || match extra {
+		[kind_0, kind_1, size_0, size_1, rest @ ..] => {
+			let kind = u16::from_le_bytes([*kind_0, *kind_1]);
+			let size = usize::from(u16::from_le_bytes([*size_0, *size_1]));
+			if size > rest.len() {
+				return None;
+			}
+			let field = &extra[..4 + size];
+			extra = &rest[size..];
+			Some((kind, field))
+		}
+		_ => None,
+	})
+}
+
+fn kept_extra_fields(extra: &[u8]) -> Vec<u8> {
+	// ZIP64, Unicode path, and alignment padding of zipalign, rezip, and Android.
+	const DROPPED: [u16; 5] = [0x0001, 0x7075, 0xa11e, 0x617a, 0xd935];
+	extra_fields(extra)
+		.filter(|(kind, _field)| !DROPPED.contains(kind))
+		.flat_map(|(_kind, field)| field)
+		.copied()
+		.collect()
+}
+
+// Info-ZIP Unix extra field of version 1 with variable-sized UID and GID.
+const UNIX_OWNER: u16 = 0x7875;
+
+// Exits before reaching the ZIP archive appended to it.
+const SFX_STUB: &[u8] = b"#!/bin/sh
+# Self-extracting ZIP archive written by rezip.
+set -e
+dir=$(mktemp -d)
+# Tolerates the warning of unzip about the leading script.
+unzip -qo \"$0\" -d \"$dir\" || [ $? -eq 1 ]
+echo \"$dir\"
+exit 0
+";
+
+fn unix_owner(extra: &[u8]) -> Option<(u32, u32)> {
+	let (_kind, field) = extra_fields(extra).find(|(kind, _field)| *kind == UNIX_OWNER)?;
+	let id = |data: &[u8]| -> Option<(u32, usize)> {
+		let (size, id) = data.split_first()?;
+		let size = usize::from(*size);
+		let mut bytes = [0; 4];
+		bytes.get_mut(..size)?.copy_from_slice(id.get(..size)?);
+		Some((u32::from_le_bytes(bytes), 1 + size))
+	};
+	match &field[4..] {
+		[1, data @ ..] => {
+			let (uid, length) = id(data)?;
+			let (gid, _length) = id(&data[length..])?;
+			Some((uid, gid))
+		}
+		_ => None,
+	}
+}
+
+fn with_unix_owner(extra: Vec<u8>, (uid, gid): (u32, u32)) -> Vec<u8> {
+	let mut kept = extra_fields(&extra)
+		.filter(|(kind, _field)| *kind != UNIX_OWNER)
+		.flat_map(|(_kind, field)| field)
+		.copied()
+		.collect::<Vec<u8>>();
+	kept.extend_from_slice(&UNIX_OWNER.to_le_bytes());
+	kept.extend_from_slice(&11u16.to_le_bytes());
+	kept.extend_from_slice(&[1, 4]);
+	kept.extend_from_slice(&uid.to_le_bytes());
+	kept.push(4);
+	kept.extend_from_slice(&gid.to_le_bytes());
+	kept
+}
+
+fn start_file_with_extra_data<W: Write + Seek>(
+	zip: &mut ZipWriter<W>,
+	name: &str,
+	options: FileOptions,
+	extra: &[u8],
+	align: u16,
+) -> ZipResult<u64> {
+	let data_start = zip.start_file_with_extra_data(name, options)?;
+	zip.write_all(extra)?;
+	let data_start = data_start + extra.len() as u64;
+	let align = u64::from(align);
+	if align > 1 && !data_start.is_multiple_of(align) {
+		let pad_length = (align - (data_start + 4) % align) % align;
+		zip.write_all(b"za")?;
+		zip.write_all(&(pad_length as u16).to_le_bytes())?;
+		zip.write_all(&vec![0; pad_length as usize])?;
+		zip.end_local_start_central_extra_data()?;
+		zip.write_all(extra)?;
+	}
+	let extra_data_end = zip.end_extra_data()?;
+	Ok(extra_data_end - data_start)
+}
+
+// File compressed with advanced parameters in a temporary ZIP archive of its own.
+struct EncodedFile {
+	name: String,
+	method: u16,
+	encoder: Encoder,
+	hasher: crc32fast::Hasher,
+	size: u64,
+}
+
+enum Encoder {
+	Zstd(zstd::stream::write::Encoder<'static, BufWriter<fs::File>>),
+	Bzip2(Bzip2Encoder<BufWriter<fs::File>>),
+}
+
+impl EncodedFile {
+	fn zstd(name: &str, level: i32, window_log: Option<u32>, workers: u32) -> io::Result<Self> {
+		let mut file = BufWriter::new(tempfile::tempfile()?);
+		start_single_file(&mut file, name, ZSTD)?;
+		let mut encoder = zstd::stream::write::Encoder::new(file, level)?;
+		if let Some(window_log) = window_log {
+			encoder.long_distance_matching(true)?;
+			encoder.window_log(window_log)?;
+		}
+		encoder.multithread(workers)?;
+		Ok(Self::new(name, ZSTD, Encoder::Zstd(encoder)))
+	}
+	fn bzip2(name: &str, level: u32, work_factor: u32) -> io::Result<Self> {
+		let mut file = BufWriter::new(tempfile::tempfile()?);
+		start_single_file(&mut file, name, BZIP2)?;
+		let encoder = Bzip2Encoder::new(file, level, work_factor);
+		Ok(Self::new(name, BZIP2, Encoder::Bzip2(encoder)))
+	}
+	fn new(name: &str, method: u16, encoder: Encoder) -> Self {
+		Self {
+			name: name.to_string(),
+			method,
+			encoder,
+			hasher: crc32fast::Hasher::new(),
+			size: 0,
+		}
+	}
+	fn finish(
+		self,
+		last_modified: DateTime,
+		unix_mode: Option<u32>,
+	) -> ZipResult<ZipArchive<BufReader<fs::File>>> {
+		let file = match self.encoder {
+			Encoder::Zstd(encoder) => encoder.finish()?,
+			Encoder::Bzip2(encoder) => encoder.finish()?,
+		};
+		let crc32 = self.hasher.finalize();
+		finish_single_file(
+			file,
+			&self.name,
+			self.method,
+			crc32,
+			self.size,
+			last_modified,
+			unix_mode,
+		)
+	}
+}
+
+impl Write for EncodedFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let length = match &mut self.encoder {
+			Encoder::Zstd(encoder) => encoder.write(buf)?,
+			Encoder::Bzip2(encoder) => encoder.write(buf)?,
+		};
+		self.hasher.update(&buf[..length]);
+		self.size += length as u64;
+		Ok(length)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		match &mut self.encoder {
+			Encoder::Zstd(encoder) => encoder.flush(),
+			Encoder::Bzip2(encoder) => encoder.flush(),
+		}
+	}
+}
+
+// Bzip2 stream of a work factor as `bzip2::write::BzEncoder` uses the default one.
+struct Bzip2Encoder<W: Write> {
+	writer: W,
+	stream: bzip2::Compress,
+	buffer: Vec<u8>,
+}
+
+impl<W: Write> Bzip2Encoder<W> {
+	fn new(writer: W, level: u32, work_factor: u32) -> Self {
+		Self {
+			writer,
+			stream: bzip2::Compress::new(bzip2::Compression::new(level), work_factor),
+			buffer: Vec::with_capacity(32 * 1024),
+		}
+	}
+	fn compress(&mut self, data: &[u8], action: bzip2::Action) -> io::Result<bzip2::Status> {
+		self.buffer.clear();
+		let status = self
+			.stream
+			.compress_vec(data, &mut self.buffer, action)
+			.map_err(io::Error::other)?;
+		self.writer.write_all(&self.buffer)?;
+		Ok(status)
+	}
+	fn finish(mut self) -> io::Result<W> {
+		while self.compress(&[], bzip2::Action::Finish)? != bzip2::Status::StreamEnd {}
+		Ok(self.writer)
+	}
+}
+
+impl<W: Write> Write for Bzip2Encoder<W> {
+	fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+		loop {
+			let total_in = self.stream.total_in();
+			self.compress(data, bzip2::Action::Run)?;
+			let length = (self.stream.total_in() - total_in) as usize;
+			if length > 0 || data.is_empty() {
+				return Ok(length);
+			}
+		}
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		while self.compress(&[], bzip2::Action::Flush)? != bzip2::Status::RunOk {}
+		self.writer.flush()
+	}
+}
+
+const BZIP2: u16 = 12;
+const ZSTD: u16 = 93;
+
+// Compresses the file into a ZIP archive of the cache, renaming it into place when finished.
+fn store_cached(
+	cached: &Path,
+	mut file: impl Read,
+	algorithm: CompressionMethod,
+	level: Option<i32>,
+) -> Result<()> {
+	let mut temporary = NamedTempFile::new_in(cached.parent().unwrap())?;
+	let mut zip = ZipWriter::new(temporary.as_file_mut());
+	let options = FileOptions::default()
+		.compression_method(algorithm)
+		.compression_level(level)
+		.large_file(true);
+	zip.start_file("data", options)?;
+	copy(&mut file, &mut zip)?;
+	zip.finish()?;
+	drop(zip);
+	temporary.persist(cached)?;
+	Ok(())
+}
+
+// Copies the compressed data of the cached file into a temporary ZIP archive of the metadata.
+fn rewrap_cached(
+	cached: &Path,
+	last_modified: DateTime,
+	unix_mode: Option<u32>,
+) -> ZipResult<ZipArchive<BufReader<fs::File>>> {
+	let mut zip = ZipArchive::new(BufReader::new(fs::File::open(cached)?))?;
+	let mut data = zip.by_index_raw(0)?;
+	let method = match data.compression() {
+		CompressionMethod::Deflated => 8,
+		CompressionMethod::Bzip2 => BZIP2,
+		CompressionMethod::Zstd => ZSTD,
+		_ => return Err(ZipError::UnsupportedArchive("Unsupported cached method")),
+	};
+	let (crc32, size) = (data.crc32(), data.size());
+	let mut file = BufWriter::new(tempfile::tempfile()?);
+	start_single_file(&mut file, "data", method)?;
+	copy(&mut data, &mut file)?;
+	finish_single_file(file, "data", method, crc32, size, last_modified, unix_mode)
+}
+
+// Removes the least recently used files of the cache exceeding its size.
+fn evict_cached(cache_dir: &Path, cache_size: u64) -> io::Result<()> {
+	let mut cached = Vec::new();
+	for entry in fs::read_dir(cache_dir)? {
+		let entry = entry?;
+		let path = entry.path();
+		if path.extension() == Some(OsStr::new("zip")) {
+			let metadata = entry.metadata()?;
+			cached.push((metadata.modified()?, metadata.len(), path));
+		}
+	}
+	cached.sort_unstable_by_key(|&(modified, _length, _)| modified);
+	let mut total = 0;
+	for (_modified, length, path) in cached.into_iter().rev() {
+		total += length;
+		if total > cache_size {
+			debug!("Evicting {:?} from cache", path);
+			fs::remove_file(&path)?;
+		}
+	}
+	Ok(())
+}
+
+// Local file header only locating the data as the central directory header describes it.
+fn start_single_file(file: &mut impl Write, name: &str, method: u16) -> io::Result<()> {
+	file.write_all(&0x04034b50u32.to_le_bytes())?;
+	file.write_all(&[63, 0, 0, 0])?;
+	file.write_all(&method.to_le_bytes())?;
+	file.write_all(&[0; 16])?;
+	file.write_all(&(name.len() as u16).to_le_bytes())?;
+	file.write_all(&[0, 0])?;
+	file.write_all(name.as_bytes())
+}
+
+// Writes the ZIP64 central directory of the file started with `start_single_file()`.
+fn finish_single_file(
+	mut file: BufWriter<fs::File>,
+	name: &str,
+	method: u16,
+	crc32: u32,
+	size: u64,
+	last_modified: DateTime,
+	unix_mode: Option<u32>,
+) -> ZipResult<ZipArchive<BufReader<fs::File>>> {
+	let directory_start = file.stream_position()?;
+	let compressed_size = directory_start - 30 - name.len() as u64;
+	let (made_by, external_attributes) = match unix_mode {
+		Some(mode) => (3 << 8 | 63, (0o100000 | mode) << 16),
+		None => (63, 0),
+	};
+	file.write_all(&0x02014b50u32.to_le_bytes())?;
+	file.write_all(&(made_by as u16).to_le_bytes())?;
+	file.write_all(&[63, 0, 0, 0])?;
+	file.write_all(&method.to_le_bytes())?;
+	file.write_all(&last_modified.timepart().to_le_bytes())?;
+	file.write_all(&last_modified.datepart().to_le_bytes())?;
+	file.write_all(&crc32.to_le_bytes())?;
+	file.write_all(&[0xff; 8])?;
+	file.write_all(&(name.len() as u16).to_le_bytes())?;
+	file.write_all(&20u16.to_le_bytes())?;
+	file.write_all(&[0; 6])?;
+	file.write_all(&external_attributes.to_le_bytes())?;
+	file.write_all(&[0; 4])?;
+	file.write_all(name.as_bytes())?;
+	file.write_all(&0x0001u16.to_le_bytes())?;
+	file.write_all(&16u16.to_le_bytes())?;
+	file.write_all(&size.to_le_bytes())?;
+	file.write_all(&compressed_size.to_le_bytes())?;
+	let directory_end = file.stream_position()?;
+	let directory_length = directory_end - directory_start;
+	file.write_all(&0x06064b50u32.to_le_bytes())?;
+	file.write_all(&44u64.to_le_bytes())?;
+	file.write_all(&[45, 0, 45, 0])?;
+	file.write_all(&[0; 8])?;
+	file.write_all(&1u64.to_le_bytes())?;
+	file.write_all(&1u64.to_le_bytes())?;
+	file.write_all(&directory_length.to_le_bytes())?;
+	file.write_all(&directory_start.to_le_bytes())?;
+	file.write_all(&0x07064b50u32.to_le_bytes())?;
+	file.write_all(&[0; 4])?;
+	file.write_all(&directory_end.to_le_bytes())?;
+	file.write_all(&1u32.to_le_bytes())?;
+	file.write_all(&0x06054b50u32.to_le_bytes())?;
+	file.write_all(&[0; 4])?;
+	file.write_all(&[1, 0, 1, 0])?;
+	file.write_all(&[0xff; 8])?;
+	file.write_all(&[0; 2])?;
+	let mut file = file.into_inner().map_err(io::IntoInnerError::into_error)?;
+	file.rewind()?;
+	ZipArchive::new(BufReader::new(file))
+}
+
+// Checks the limits of the next file or, without name, of the central directory.
+fn check_zip64(path: &Path, name: Option<&Path>, entries: usize, offset: u64) -> Result<()> {
+	match name {
+		Some(name) if offset > u64::from(u32::MAX) => {
+			Err(eyre!("Cannot write {:?} beyond 4 GiB without ZIP64", name))
+		}
+		None if offset > u64::from(u32::MAX) => Err(eyre!(
+			"Cannot write central directory beyond 4 GiB without ZIP64"
+		)),
+		Some(name) if entries >= usize::from(u16::MAX) => Err(eyre!(
+			"Cannot write {:?} exceeding 65535 files without ZIP64",
+			name
+		)),
+		_ => Ok(()),
+	}
+	.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))
+}
+
+fn check_name(name: &Path, max_length: Option<usize>, max_depth: Option<usize>) -> Result<()> {
+	let length = name.as_os_str().len();
+	if let Some(max_length) = max_length.filter(|&max_length| length > max_length) {
+		return Err(eyre!(
+			"File name {:?} of {} bytes exceeds {} bytes",
+			name,
+			length,
+			max_length
+		));
+	}
+	if let Some(max_depth) = max_depth {
+		let mut depth = 0;
+		for component in name.components() {
+			match component {
+				Component::Normal(_) => depth += 1,
+				Component::CurDir => {}
+				_ => return Err(eyre!("Unsafe file name {:?}", name)),
+			}
+		}
+		if depth > max_depth {
+			return Err(eyre!(
+				"File name {:?} of depth {} exceeds depth {}",
+				name,
+				depth,
+				max_depth
+			));
+		}
+	}
+	Ok(())
+}
+
+// Selects the method of --recompress auto unless other globs match.
+fn select_method<T>(
+	zips: &mut Inputs,
+	(input, index): (usize, usize),
+	(name, from): (&Path, Option<&Path>),
+	recompress: &[(Glob, Option<T>)],
+	automatic: &[(Glob, Option<()>)],
+	options: GlobOptions,
+) -> Result<Option<(CompressionMethod, &'static str)>> {
+	let explicit = recompress
+		.iter()
+		.any(|(glob, _method)| options.matches(glob, name, from));
+	if explicit || match_input_glob_value(automatic, name, from, options).is_none() {
+		return Ok(None);
+	}
+	let file = zips.get(input)?.by_index(index)?;
+	if file.is_dir() {
+		return Ok(None);
+	}
+	let extension = name
+		.extension()
+		.and_then(OsStr::to_str)
+		.map(str::to_ascii_lowercase)
+		.unwrap_or_default();
+	if COMPRESSED_EXTENSIONS.contains(&extension.as_str()) {
+		return Ok(Some((CompressionMethod::Stored, "compressed by extension")));
+	}
+	let mut sample = Vec::new();
+	file.take(64 * 1024)
+		.read_to_end(&mut sample)
+		.wrap_err_with(|| format!("Cannot read {:?}", name))?;
+	if sample.is_empty() {
+		return Ok(Some((CompressionMethod::Stored, "empty")));
+	}
+	let mut counts = [0usize; 256];
+	for byte in &sample {
+		counts[usize::from(*byte)] += 1;
+	}
+	let entropy = counts
+		.iter()
+		.filter(|&&count| count > 0)
+		.map(|&count| {
+			let probability = count as f64 / sample.len() as f64;
+			-probability * probability.log2()
+		})
+		.sum::<f64>();
+	if entropy > 7.5 {
+		Ok(Some((CompressionMethod::Stored, "high entropy")))
+	} else {
+		Ok(Some((CompressionMethod::Zstd, "low entropy")))
+	}
+}
+
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+	"7z", "apk", "avif", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jar", "jpeg", "jpg",
+	"lz4", "m4a", "mkv", "mp3", "mp4", "ogg", "opus", "png", "pptx", "rar", "tgz", "tzst", "webm",
+	"webp", "woff2", "xlsx", "xz", "zip", "zst",
+];
+
+fn input_globs(inputs: &[String]) -> Result<Vec<String>> {
+	let mut globs = Vec::new();
+	for input in inputs {
+		match input.strip_prefix('@') {
+			Some(list) => {
+				let list = fs::read_to_string(list)
+					.wrap_err_with(|| format!("Cannot read input list {:?}", list))?;
+				globs.extend(
+					list.lines()
+						.map(str::trim)
+						.filter(|line| !line.is_empty() && !line.starts_with('#'))
+						.map(str::to_string),
+				);
+			}
+			None => globs.push(input.clone()),
+		}
+	}
+	Ok(globs)
+}
+
+fn input_paths(
+	inputs: &[String],
+	input_order: InputOrder,
+	mut skip: impl FnMut(Report) -> Result<()>,
+) -> Result<Vec<PathBuf>> {
+	let mut paths = Vec::new();
+	for glob in &input_globs(inputs)? {
+		let inputs =
+			glob_expand(glob).wrap_err_with(|| format!("Invalid glob pattern {:?}", glob))?;
+		for path in inputs {
+			match path.wrap_err_with(|| format!("Cannot read matches of {:?}", glob)) {
+				Ok(path) => paths.push(path),
+				Err(err) => skip(err)?,
+			}
+		}
+	}
+	match input_order {
+		InputOrder::Asis => {}
+		InputOrder::Name => paths.sort(),
+		InputOrder::Natural => {
+			paths.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+		}
+		InputOrder::Mtime => paths.sort_by_cached_key(|path| {
+			fs::metadata(path)
+				.and_then(|metadata| metadata.modified())
+				.ok()
+		}),
+	}
+	Ok(paths)
+}
+
+// Merges the regular files matched by each input glob under the group name derived from it.
+fn glob_groups(inputs: &[String]) -> Result<Vec<(Glob, Option<String>)>> {
+	const METACHARACTERS: [char; 3] = ['*', '?', '['];
+	let mut groups = Vec::new();
+	for glob in input_globs(inputs)? {
+		let file_name = glob.rsplit('/').next().unwrap_or_default();
+		let name = match (
+			file_name.find(METACHARACTERS),
+			file_name.rfind(METACHARACTERS),
+		) {
+			(Some(first), Some(last)) => {
+				let stem = file_name[..first].trim_end_matches(['-', '_', '.']);
+				let extension = file_name[last..]
+					.find('.')
+					.map_or("", |dot| &file_name[last + dot..]);
+				if stem.is_empty() {
+					return Err(eyre!("Cannot derive group name of input glob {:?}", glob));
+				}
+				format!("{}{}", stem, extension)
+			}
+			_ => file_name.to_string(),
+		};
+		let paths =
+			glob_expand(&glob).wrap_err_with(|| format!("Invalid glob pattern {:?}", glob))?;
+		for path in paths.flatten().filter(|path| path.is_file()) {
+			let path = path
+				.to_str()
+				.ok_or_else(|| eyre!("Invalid input path {:?}", path))?;
+			let glob = Glob {
+				from: None,
+				name: Pattern::new(&Pattern::escape(path))
+					.wrap_err_with(|| format!("Invalid glob pattern {:?}", path))?,
+			};
+			groups.push((glob, Some(name.clone())));
+		}
+	}
+	Ok(groups)
+}
+
+// Reads the arrays of <[glob=]value> pairs of --rules by option.
+fn read_rules(path: &Path) -> Result<HashMap<String, Vec<String>>> {
+	const KEYS: [&str; 4] = ["recompress", "align", "stack", "merge"];
+	let rules = fs::read_to_string(path)?.parse::<toml::Table>()?;
+	let mut pairs = HashMap::new();
+	for (key, value) in rules {
+		if !KEYS.contains(&key.as_str()) {
+			return Err(eyre!("Unsupported key {:?}", key));
+		}
+		let values = value
+			.as_array()
+			.and_then(|values| {
+				values
+					.iter()
+					.map(|value| value.as_str().map(str::to_string))
+					.collect::<Option<Vec<_>>>()
+			})
+			.ok_or_else(|| eyre!("Invalid value of {:?} instead of array of strings", key))?;
+		pairs.insert(key, values);
+	}
+	Ok(pairs)
+}
+
+fn check_skipped(skipped: usize) -> Result<()> {
+	if skipped > 0 {
+		let plural = if skipped > 1 { "s" } else { "" };
+		Err(eyre!("Skipped {} unreadable input{}", skipped, plural))
+	} else {
+		Ok(())
+	}
+}
+
+impl Rezip {
+	/// Maximum log level of `rezip` as requested by the verbosity if given at all.
+	///
+	/// Is info, debug, or trace level for one, two, or three occurrences, or off if quiet.
+	pub fn log_level(&self) -> Option<LevelFilter> {
+		match self.verbose {
+			_ if self.quiet => Some(LevelFilter::Off),
+			0 => None,
+			1 => Some(LevelFilter::Info),
+			2 => Some(LevelFilter::Debug),
+			_ => Some(LevelFilter::Trace),
+		}
+	}
+	/// Merges or checks input ZIP archives while logging status information.
+	///
+	/// Logs in the given log format with the [`log`] crate, leaving the filtering by log level to
+	/// the installed logger, see [`Self::log_level()`].
+	pub fn run(&self) -> Result<()> {
+		let log = Log {
+			format: self.log_format,
+		};
+		self.run_with_progress(|event| log.event(event))
+	}
+	/// Merges or checks input ZIP archives while passing status information to `on_event`.
+	///
+	/// Ignores the verbosity and the log format as `on_event` receives all events.
+	pub fn run_with_progress(&self, on_event: impl FnMut(RezipEvent<'_>)) -> Result<()> {
+		match &self.extract {
+			Some(dir) => extract(self, dir, on_event),
+			None if self.filter => filter(self, on_event),
+			None if self
+				.output
+				.as_ref()
+				.is_some_and(|output| output.to_string_lossy().contains("{stem}")) =>
+			{
+				run_per_input(self, on_event)
+			}
+			None => run(self, on_event),
+		}
+	}
+}
+
+fn run_per_input(rezip: &Rezip, mut on_event: impl FnMut(RezipEvent<'_>)) -> Result<()> {
+	let template = rezip.output.as_deref().unwrap().to_string_lossy();
+	let mut skipped = 0;
+	let skip = |err: Report| {
+		if rezip.continue_on_error {
+			warn!("Skipping as {:#}", err);
+			skipped += 1;
+			Ok(())
+		} else {
+			Err(err)
+		}
+	};
+	let paths = input_paths(&rezip.inputs, rezip.input_order, skip)?;
+	let mut outputs = HashMap::<PathBuf, &Path>::new();
+	for path in &paths {
+		let stem = path
+			.file_stem()
+			.and_then(OsStr::to_str)
+			.ok_or_else(|| eyre!("Invalid file stem of {:?}", path))?;
+		let output = PathBuf::from(template.replace("{stem}", stem));
+		if let Some(other) = outputs.insert(output.clone(), path) {
+			return Err(eyre!(
+				"Cannot write output ZIP archive {:?} of both {:?} and {:?}",
+				output,
+				other,
+				path
+			));
+		}
+	}
+	let mut failed = Vec::new();
+	for path in &paths {
+		let stem = path.file_stem().and_then(OsStr::to_str).unwrap();
+		let input = path
+			.to_str()
+			.ok_or_else(|| eyre!("Invalid input path {:?}", path))?;
+		let output = PathBuf::from(template.replace("{stem}", stem));
+		let single = Rezip {
+			inputs: vec![Pattern::escape(input)],
+			output: Some(output.clone()),
+			..rezip.clone()
+		};
+		match run(&single, &mut on_event) {
+			Err(err) if rezip.no_fail_fast => failed.push((output, err)),
+			result => result?,
+		}
+	}
+	if !failed.is_empty() {
+		let errors = failed
+			.iter()
+			.map(|(output, err)| format!("\n{:?}: {:#}", output, err))
+			.collect::<String>();
+		let plural = if paths.len() > 1 { "s" } else { "" };
+		return Err(eyre!(
+			"Cannot write {} of {} output ZIP archive{}:{}",
+			failed.len(),
+			paths.len(),
+			plural,
+			errors
+		));
+	}
+	check_skipped(skipped)
+}
+
+fn filter(rezip: &Rezip, mut on_event: impl FnMut(RezipEvent<'_>)) -> Result<()> {
+	let mut input = tempfile::Builder::new()
+		.prefix("stdin.tmp-")
+		.suffix(".zip")
+		.tempfile()
+		.wrap_err("Cannot create temporary file")?;
+	copy(&mut io::stdin().lock(), input.as_file_mut())
+		.wrap_err("Cannot read input ZIP archive from stdin")?;
+	let input = input.into_temp_path();
+	let output = tempfile::Builder::new()
+		.prefix("stdout.tmp-")
+		.suffix(".zip")
+		.tempfile()
+		.wrap_err("Cannot create temporary file")?
+		.into_temp_path();
+	let path = input
+		.to_str()
+		.ok_or_else(|| eyre!("Invalid input path {:?}", input))?;
+	let archive = Rezip {
+		inputs: vec![Pattern::escape(path)],
+		output: Some(output.to_path_buf()),
+		filter: false,
+		force: true,
+		..rezip.clone()
+	};
+	run(&archive, &mut on_event)?;
+	let mut zip = fs::File::open(&output)
+		.wrap_err_with(|| format!("Cannot read temporary ZIP archive {:?}", output))?;
+	let mut stdout = io::stdout().lock();
+	copy(&mut zip, &mut stdout)
+		.and_then(|_length| stdout.flush())
+		.wrap_err("Cannot write output ZIP archive to stdout")
+}
+
+fn extract(rezip: &Rezip, dir: &Path, mut on_event: impl FnMut(RezipEvent<'_>)) -> Result<()> {
+	let parent = dir
+		.parent()
+		.filter(|parent| !parent.as_os_str().is_empty())
+		.unwrap_or_else(|| Path::new("."));
+	let mut prefix = dir.file_name().unwrap_or_default().to_owned();
+	prefix.push(".tmp-");
+	let temporary = tempfile::Builder::new()
+		.prefix(&prefix)
+		.suffix(".zip")
+		.tempfile_in(parent)
+		.wrap_err_with(|| format!("Cannot create temporary ZIP archive in {:?}", parent))?
+		.into_temp_path();
+	let archive = Rezip {
+		output: Some(temporary.to_path_buf()),
+		extract: None,
+		force: true,
+		..rezip.clone()
+	};
+	run(&archive, &mut on_event)?;
+	let mut zip = fs::File::open(&temporary)
+		.map(BufReader::new)
+		.map_err(ZipError::from)
+		.and_then(ZipArchive::new)
+		.wrap_err_with(|| format!("Cannot read temporary ZIP archive {:?}", temporary))?;
+	fs::create_dir_all(dir).wrap_err_with(|| format!("Cannot create directory {:?}", dir))?;
+	for index in 0..zip.len() {
+		let mut file = zip.by_index(index)?;
+		let name = file
+			.enclosed_name()
+			.map(Path::to_path_buf)
+			.ok_or_else(|| eyre!("Cannot extract unsafe name {:?}", file.name()))?;
+		let path = dir.join(&name);
+		on_event(RezipEvent::Extracting {
+			name: &name,
+			to: &path,
+		});
+		if file.is_dir() {
+			fs::create_dir_all(&path)
+				.wrap_err_with(|| format!("Cannot create directory {:?}", path))?;
+			continue;
+		}
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)
+				.wrap_err_with(|| format!("Cannot create directory {:?}", parent))?;
+		}
+		let mut extracted = OpenOptions::new()
+			.write(true)
+			.create(rezip.force)
+			.truncate(rezip.force)
+			.create_new(!rezip.force)
+			.open(&path)
+			.wrap_err_with(|| format!("Cannot create file {:?}", path))?;
+		copy(&mut file, &mut extracted).wrap_err_with(|| format!("Cannot extract {:?}", name))?;
+		if let Ok(time) = file.last_modified().to_time() {
+			extracted
+				.set_modified(time.into())
+				.wrap_err_with(|| format!("Cannot set modification time of {:?}", path))?;
+		}
+		#[cfg(unix)]
+		if let Some(mode) = file.unix_mode() {
+			use std::os::unix::fs::PermissionsExt;
+			fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+				.wrap_err_with(|| format!("Cannot set permissions of {:?}", path))?;
+		}
+	}
+	Ok(())
+}
+
+fn run(rezip: &Rezip, mut on_event: impl FnMut(RezipEvent<'_>)) -> Result<()> {
+	let Rezip {
+		sfx,
+		list,
+		list_json,
+		report_methods,
+		force,
+		update,
+		continue_on_error,
+		collision,
+		preserve_order,
+		rename_collisions,
+		max_open_inputs,
+		follow_links,
+		max_entries,
+		stack_order,
+		on_missing_stack_member,
+		max_stack_inputs,
+		..
+	} = *rezip;
+	let started = Instant::now();
+	let parsed = parse_options(rezip)?;
+	let Parsed {
+		merge,
+		merge_mode,
+		merge_time,
+		raw_spec,
+		stack_cmd,
+		stack,
+		cache_dir,
+		..
+	} = &parsed;
+	let match_options = parsed.match_options;
+	if let Some(cache_dir) = cache_dir {
+		fs::create_dir_all(cache_dir)
+			.wrap_err_with(|| format!("Cannot create cache directory {:?}", cache_dir))?;
+	}
+	let previous = rezip
+		.diff_against
+		.as_deref()
+		.map(|path| {
+			digest_files(path)
+				.wrap_err_with(|| format!("Cannot read previous ZIP archive {:?}", path))
+		})
+		.transpose()?;
+	let updated = rezip
+		.output
+		.as_deref()
+		.filter(|path| update && path.exists());
+	let force = force || updated.is_some();
+	let zip = rezip
+		.output
+		.as_ref()
+		.map(|path| {
+			create_output(
+				path,
+				rezip.tmpdir.as_deref(),
+				force,
+				sfx.then_some(SFX_STUB),
+			)
+		})
+		.transpose()?;
+	let mut skipped = 0;
+	let skip = |err: Report| {
+		if continue_on_error {
+			warn!("Skipping as {:#}", err);
+			skipped += 1;
+			Ok(())
+		} else {
+			Err(err)
+		}
+	};
+	let dir_options = DirOptions {
+		follow_links,
+		base: rezip.base.as_deref(),
+		modes: merge_mode,
+		times: merge_time,
+	};
+	let mut zips = Inputs::new(merge, match_options, dir_options, max_open_inputs);
+	let mut timings = Timings::default();
+	let comment = match &rezip.comment_file {
+		Some(path) => {
+			let comment =
+				fs::read(path).wrap_err_with(|| format!("Cannot read comment file {:?}", path))?;
+			if comment.len() > usize::from(u16::MAX) {
+				return Err(eyre!(
+					"Cannot comment with {} bytes exceeding 65535 bytes",
+					comment.len()
+				))
+				.wrap_err_with(|| format!("Invalid comment file {:?}", path));
+			}
+			comment
+		}
+		None => Vec::new(),
+	};
+	let trailer = match &rezip.append_trailer {
+		Some(path) => {
+			let trailer =
+				fs::read(path).wrap_err_with(|| format!("Cannot read trailer file {:?}", path))?;
+			if trailer.len() + comment.len() > usize::from(u16::MAX) {
+				return Err(eyre!(
+					"Cannot append {} bytes exceeding 65535 bytes with comment",
+					trailer.len()
+				))
+				.wrap_err_with(|| format!("Invalid trailer file {:?}", path));
+			}
+			trailer
+		}
+		None => Vec::new(),
+	};
+	let mut files = index_inputs(
+		rezip,
+		&parsed,
+		updated,
+		&mut zips,
+		&mut timings,
+		skip,
+		&mut on_event,
+	)?;
+	let mut inputs = zips.paths.clone();
+	let mut raw_files = Vec::new();
+	if !raw_spec.is_empty() {
+		let mut converted = IndexMap::<_, Vec<_>>::new();
+		for (name, group) in mem::take(&mut files) {
+			let spec = match group.first() {
+				Some(&(input, index)) if !zips.get(input)?.by_index(index)?.is_dir() => {
+					match_glob_value(raw_spec, &name, match_options)
+				}
+				_ => None,
+			};
+			let Some(spec) = spec else {
+				converted.entry(name).or_default().extend(group);
+				continue;
+			};
+			let npy = name.with_extension("npy");
+			for (input, index) in group {
+				let raw = raw_npy(&mut zips, (input, index), &npy, &spec).wrap_err_with(|| {
+					format!("Cannot read {:?} in {:?} as NPY file", name, inputs[input])
+				})?;
+				let zip = zips.open(&raw)?;
+				converted
+					.entry(npy.clone())
+					.or_default()
+					.push((zips.paths.len(), 0));
+				zips.push(raw.to_path_buf(), zip);
+				inputs.push(inputs[input].clone());
+				raw_files.push(raw);
+			}
+		}
+		files = converted;
+	}
+	let is_stacked = |name: &Path| {
+		let stacked = match Format::new(name) {
+			Some(Format::Npy | Format::NpyGz) => npy_axis(stack, name, match_options).is_some(),
+			Some(_) => match_glob_value(stack, name, match_options).is_some(),
+			None => false,
+		};
+		stacked || match_glob_value(stack_cmd, name, match_options).is_some()
+	};
+	if rename_collisions {
+		let names = files.keys().cloned().collect::<HashSet<_>>();
+		let mut numbered = IndexMap::<_, Vec<_>>::new();
+		for (name, group) in mem::take(&mut files) {
+			let stacked = is_stacked(&name);
+			let is_dir = match group.first() {
+				Some(&(input, index)) => zips.get(input)?.by_index(index)?.is_dir(),
+				None => true,
+			};
+			if group.len() < 2 || stacked || is_dir {
+				numbered.entry(name).or_default().extend(group);
+				continue;
+			}
+			let mut number = 0;
+			for (input, index) in group {
+				let mut to = name.clone();
+				while number > 0 && (names.contains(&to) || numbered.contains_key(&to)) {
+					to = number_name(&name, number);
+					number += 1;
+				}
+				number = number.max(1);
+				if to != name {
+					on_event(RezipEvent::Numbering {
+						name: &name,
+						to: &to,
+						from: &inputs[input],
+					});
+				}
+				numbered.entry(to).or_default().push((input, index));
+			}
+		}
+		files = numbered;
+	}
+	if preserve_order {
+		// Keys directories added for nested files by the file following them.
+		let mut keys = Vec::with_capacity(files.len());
+		let mut next = (usize::MAX, usize::MAX);
+		for files in files.values().rev() {
+			if !files.is_empty() {
+				next = select_file(&mut zips, files, collision.unwrap_or(CollisionPolicy::Last))?;
+			}
+			keys.push(next);
+		}
+		keys.reverse();
+		let mut ordered = keys
+			.into_iter()
+			.zip(mem::take(&mut files))
+			.collect::<Vec<_>>();
+		ordered.sort_by_key(|&(key, _)| key);
+		files = ordered.into_iter().map(|(_key, file)| file).collect();
+	}
+	if let Some(max_stack_inputs) = max_stack_inputs {
+		for (name, files) in &files {
+			if files.len() > max_stack_inputs && is_stacked(name) {
+				return Err(eyre!(
+					"Cannot stack {} files of {:?} exceeding {} files",
+					files.len(),
+					name,
+					max_stack_inputs
+				));
+			}
+		}
+	}
+	if let Some(max_entries) = max_entries.filter(|&max_entries| files.len() > max_entries) {
+		return Err(eyre!(
+			"Cannot write {} files exceeding {} files",
+			files.len(),
+			max_entries
+		));
+	}
+	let mut stacking = Vec::new();
+	if let Some(policy) = on_missing_stack_member {
+		if policy == MissingPolicy::Zero
+			&& (stack_order.is_some() || rezip.stack_by_index.is_some())
+		{
+			return Err(eyre!(
+				"Cannot sort zero-filled arrays of missing NPY files to be stacked"
+			));
+		}
+		for (name, files) in &files {
+			if npy_axis(stack, name, match_options).is_some() {
+				stacking.extend(files.iter().map(|&(input, _index)| input));
+			}
+		}
+		stacking.sort_unstable();
+		stacking.dedup();
+	}
+	if let Some((path, zip)) = rezip.output.as_deref().zip(zip) {
+		let mut state = OutputState::new(path, zip, force, comment, trailer);
+		for (name, files) in &files {
+			write_entry(
+				rezip,
+				&parsed,
+				&mut state,
+				(name, files),
+				&stacking,
+				&mut inputs,
+				&mut zips,
+				&mut timings,
+				&mut on_event,
+			)?;
+		}
+		finish_writing(rezip, &parsed, state, previous, &mut on_event)?;
+		report_timings(path, &inputs, &timings, started, &mut on_event);
+	} else if report_methods {
+		list_methods(&inputs, &mut zips, &mut on_event)?;
+	} else if list {
+		list_files(rezip, &parsed, &inputs, &mut zips)?;
+	} else if list_json {
+		list_files_json(rezip, &parsed, &inputs, &mut zips)?;
+	} else {
+		check_files(rezip, &parsed, &files, &inputs, &mut zips, &mut on_event)?;
+	}
+	check_skipped(skipped)
+}
+
+// Options parsed into glob-value pairs, extended by the ones of the rules file if any.
+struct Parsed {
+	match_options: GlobOptions,
+	merge: Vec<(Glob, Option<String>)>,
+	merge_mode: Vec<(Glob, Option<u32>)>,
+	merge_time: Vec<(Glob, Option<DateTime>)>,
+	prefix: Vec<(Glob, Option<String>)>,
+	rename: Vec<(Regex, String)>,
+	automatic: Vec<(Glob, Option<()>)>,
+	recompress: Vec<(Glob, Option<Recompression>)>,
+	precompress: Vec<(Glob, Option<u32>)>,
+	aligned_compressed: Option<i32>,
+	align: Vec<(Glob, Option<u16>)>,
+	raw_spec: Vec<(Glob, Option<RawSpec>)>,
+	stack_cmd: Vec<(Glob, Option<String>)>,
+	stack: Vec<(Glob, Option<usize>)>,
+	stack_shape: StackShape,
+	zip64: Zip64Mode,
+	cache_dir: Option<PathBuf>,
+}
+
+// Compression method with optional level and bzip2 work factor.
+type Recompression = (CompressionMethod, Option<i32>, Option<u32>);
+
+fn parse_options(rezip: &Rezip) -> Result<Parsed> {
+	let Rezip {
+		ignore_case,
+		match_basename,
+		glob_as_group,
+		align_arbitrary,
+		repack_only,
+		zstd_long,
+		flatten_stack,
+		stack_broadcast,
+		no_zip64,
+		zip64,
+		no_cache,
+		..
+	} = *rezip;
+	let (recompress, align, stack, merge) = (
+		rezip.recompress.clone(),
+		rezip.align.clone(),
+		rezip.stack.clone(),
+		rezip.merge.clone(),
+	);
+	let (recompress, align, stack, merge) = match &rezip.rules {
+		Some(path) => {
+			let mut rules =
+				read_rules(path).wrap_err_with(|| format!("Invalid rules file {:?}", path))?;
+			let mut rules = |key, given, defaults| {
+				let mut pairs = rules.remove(key).unwrap_or_default();
+				if pairs.is_empty() {
+					given
+				} else {
+					if given != defaults {
+						pairs.extend(given);
+					}
+					pairs
+				}
+			};
+			(
+				rules("recompress", recompress, DEFAULT_RECOMPRESS),
+				rules("align", align, DEFAULT_ALIGN),
+				rules("stack", stack, DEFAULT_STACK),
+				rules("merge", merge, &[]),
+			)
+		}
+		None => (recompress, align, stack, merge),
+	};
+	let match_options = GlobOptions {
+		options: MatchOptions {
+			case_sensitive: !ignore_case,
+			..MatchOptions::new()
+		},
+		basename: match_basename,
+	};
+	let merge = parse_glob_value(&merge, |name| Ok(name.to_string()))?;
+	let merge = if glob_as_group {
+		let mut groups = glob_groups(&rezip.inputs)?;
+		groups.extend(merge);
+		groups
+	} else {
+		merge
+	};
+	let merge_mode = parse_glob_value(&rezip.merge_mode, |mode| {
+		u32::from_str_radix(mode, 8)
+			.ok()
+			.filter(|&mode| mode <= 0o7777)
+			.ok_or_else(|| eyre!("Invalid mode {:?}", mode))
+	})?;
+	let merge_time = parse_glob_value(&rezip.merge_time, |seconds| {
+		seconds
+			.parse()
+			.ok()
+			.and_then(|seconds| OffsetDateTime::from_unix_timestamp(seconds).ok())
+			.and_then(|time| DateTime::try_from(time).ok())
+			.ok_or_else(|| eyre!("Invalid time {:?} before 1980 or after 2107", seconds))
+	})?;
+	let prefix = parse_glob_value(&rezip.prefix, |path| {
+		let path = path.trim_matches('/');
+		if path.is_empty() {
+			Err(eyre!("Invalid prefix {:?}", path))
+		} else {
+			Ok(path.to_string())
+		}
+	})?;
+	let rename = rezip
+		.rename
+		.iter()
+		.map(|rename| {
+			let (regex, replacement) = split_rename(rename)
+				.ok_or_else(|| eyre!("Missing replacement"))
+				.wrap_err_with(|| format!("Invalid rename {:?}", rename))?;
+			Regex::new(&regex)
+				.map(|regex| (regex, replacement.to_string()))
+				.wrap_err_with(|| format!("Invalid regular expression {:?}", regex))
+		})
+		.collect::<Result<Vec<_>>>()?;
+	let (automatic, recompress): (Vec<_>, Vec<_>) = recompress
+		.into_iter()
+		.partition(|method| method.rsplit('=').next() == Some("auto"));
+	let automatic = parse_glob_value(&automatic, |_method| Ok(()))?;
+	let recompress = recompress
+		.into_iter()
+		.map(|method| match method.strip_suffix("keep") {
+			Some(glob) if glob.is_empty() || glob.ends_with('=') => glob.to_string(),
+			_ => method,
+		})
+		.collect::<Vec<_>>();
+	let recompress = parse_glob_value(&recompress, |method| {
+		let mut parameters = method.split(':');
+		let (algorithm, level) = (parameters.next(), parameters.next());
+		match (algorithm, level) {
+			(Some("stored"), None) => Ok((CompressionMethod::Stored, None, None)),
+			(Some("deflated"), level) => level
+				.map(|level| parse_level(CompressionMethod::Deflated, level, method))
+				.transpose()
+				.map(|level| (CompressionMethod::Deflated, level, None)),
+			(Some("bzip2"), level) => {
+				let (level, work_factor) = match level.map(|level| level.split_once(',')) {
+					Some(Some((level, work_factor))) => (Some(level), Some(work_factor)),
+					Some(None) => (level, None),
+					None => (None, None),
+				};
+				let level = level
+					.map(|level| parse_level(CompressionMethod::Bzip2, level, method))
+					.transpose();
+				let work_factor = work_factor
+					.map(|work_factor| {
+						work_factor
+							.parse::<u32>()
+							.map_err(From::from)
+							.and_then(|work_factor| {
+								if (1..=250).contains(&work_factor) {
+									Ok(work_factor)
+								} else {
+									Err(eyre!("Invalid work factor in {:?}", method))
+								}
+							})
+					})
+					.transpose();
+				level.and_then(|level| {
+					work_factor.map(|work_factor| (CompressionMethod::Bzip2, level, work_factor))
+				})
+			}
+			(Some("zstd"), level) => level
+				.map(|level| parse_level(CompressionMethod::Zstd, level, method))
+				.transpose()
+				.map(|level| (CompressionMethod::Zstd, level, None)),
+			(Some(_), _) => Err(eyre!("Unsupported method {:?}", method)),
+			_ => Err(eyre!("Invalid method {:?}", method)),
+		}
+		.wrap_err_with(|| format!("Invalid recompress method {:?}", method))
+	})?;
+	let precompress = parse_glob_value(&rezip.precompress, |codec| {
+		let mut parameters = codec.split(':');
+		let (kind, level) = (parameters.next(), parameters.next());
+		match (kind, level) {
+			(Some("xz"), level) => level
+				.map(|level| {
+					level.parse::<u32>().map_err(From::from).and_then(|level| {
+						if level <= 9 {
+							Ok(level)
+						} else {
+							Err(eyre!("Invalid level in {:?}", codec))
+						}
+					})
+				})
+				.transpose()
+				.map(|level| level.unwrap_or(6)),
+			(Some(_), _) => Err(eyre!("Unsupported codec {:?}", codec)),
+			_ => Err(eyre!("Invalid codec {:?}", codec)),
+		}
+		.wrap_err_with(|| format!("Invalid precompress codec {:?}", codec))
+	})?;
+	let aligned_compressed = rezip
+		.aligned_compressed
+		.as_ref()
+		.map(|codec| {
+			let mut parameters = codec.split(':');
+			let (kind, level) = (parameters.next(), parameters.next());
+			match (kind, level) {
+				(Some("zstd"), level) => level
+					.map(|level| parse_level(CompressionMethod::Zstd, level, codec))
+					.transpose()
+					.map(|level| level.unwrap_or(3)),
+				(Some(_), _) => Err(eyre!("Unsupported codec {:?}", codec)),
+				_ => Err(eyre!("Invalid codec {:?}", codec)),
+			}
+			.wrap_err_with(|| format!("Invalid aligned-compressed codec {:?}", codec))
+		})
+		.transpose()?;
+	let align = parse_glob_value(&align, |bytes| {
+		bytes
+			.parse::<u16>()
+			.map_err(From::from)
+			.and_then(|bytes| {
+				if bytes == 0 {
+					Err(eyre!("Must be positive"))
+				} else if align_arbitrary || bytes & bytes.wrapping_sub(1) == 0 {
+					Ok(bytes)
+				} else {
+					Err(eyre!("Must be a power of two"))
+				}
+			})
+			.wrap_err_with(|| format!("Invalid align bytes {:?}", bytes))
+	})?;
+	let raw_spec = parse_glob_value(&rezip.raw_spec, |path| {
+		read_raw_spec(Path::new(path)).wrap_err_with(|| format!("Invalid raw spec file {:?}", path))
+	})?;
+	let stack_cmd = parse_glob_value_at(
+		&rezip.stack_cmd,
+		|value| value.find('='),
+		|command| {
+			if command.split_whitespace().next().is_some() {
+				Ok(command.to_string())
+			} else {
+				Err(eyre!("Invalid stack command {:?}", command))
+			}
+		},
+	)?;
+	let stack = if repack_only { &[] } else { stack.as_slice() };
+	let stack = parse_glob_value(stack, |axis| {
+		axis.parse()
+			.wrap_err_with(|| format!("Invalid stack axis {:?}", axis))
+	})?;
+	if let Some(window_log) = zstd_long.filter(|window_log| !(10..=27).contains(window_log)) {
+		return Err(eyre!("Invalid zstd window log {}", window_log));
+	}
+	let stack_shape = if flatten_stack {
+		StackShape::Flatten
+	} else if stack_broadcast {
+		StackShape::Broadcast
+	} else {
+		StackShape::Keep
+	};
+	Ok(Parsed {
+		match_options,
+		merge,
+		merge_mode,
+		merge_time,
+		prefix,
+		rename,
+		automatic,
+		recompress,
+		precompress,
+		aligned_compressed,
+		align,
+		raw_spec,
+		stack_cmd,
+		stack,
+		stack_shape,
+		zip64: if no_zip64 { Zip64Mode::Never } else { zip64 },
+		cache_dir: rezip.cache_dir.clone().filter(|_dir| !no_cache),
+	})
+}
+
+// Indexes the files of the input ZIP archives by their output names, starting with the ones of the
+// updated output ZIP archive if any.
+fn index_inputs(
+	rezip: &Rezip,
+	parsed: &Parsed,
+	updated: Option<&Path>,
+	zips: &mut Inputs,
+	timings: &mut Timings,
+	mut skip: impl FnMut(Report) -> Result<()>,
+	mut on_event: impl FnMut(RezipEvent<'_>),
+) -> Result<IndexMap<PathBuf, Vec<(usize, usize)>>> {
+	let Rezip {
+		input_order,
+		repack_only,
+		allow_duplicate_inputs,
+		normalize_paths,
+		utf8_names,
+		strip_components,
+		no_dir_entries,
+		dedup_dirs,
+		min_size,
+		max_size,
+		max_name_length,
+		max_depth,
+		..
+	} = *rezip;
+	let Parsed {
+		merge,
+		prefix,
+		rename,
+		..
+	} = parsed;
+	let match_options = parsed.match_options;
+	let mut files = IndexMap::<_, Vec<_>>::new();
+	let updated = match updated {
+		Some(path) => {
+			let indexed = Instant::now();
+			let mut zip = zips.open(path)?;
+			let input = zips.paths.len();
+			on_event(RezipEvent::Indexing {
+				from: path,
+				files: zip.len(),
+			});
+			for index in 0..zip.len() {
+				let file = zip.by_index(index).wrap_err_with(|| {
+					format!(
+						"Cannot read file[{}] in output ZIP archive {:?}",
+						index, path
+					)
+				})?;
+				if no_dir_entries && file.is_dir() {
+					continue;
+				}
+				let name = if utf8_names {
+					file.utf8_name().unwrap_or_else(|| file.name())
+				} else {
+					file.name()
+				};
+				files
+					.entry(name.to_path_buf())
+					.or_default()
+					.push((input, index));
+			}
+			zips.push(path.to_path_buf(), zip);
+			timings.indexing.insert(input, indexed.elapsed());
+			Some(input)
+		}
+		None => None,
+	};
+	let mut dirs = HashMap::new();
+	let paths = input_paths(&rezip.inputs, input_order, &mut skip)?;
+	if repack_only && paths.len() != 1 {
+		return Err(eyre!(
+			"Cannot repack {} input ZIP archives instead of one",
+			paths.len()
+		));
+	}
+	for path in paths {
+		let indexed = Instant::now();
+		let (path, mut zip) = match zips.open(&path) {
+			Ok(zip) => (path, zip),
+			Err(err) => {
+				skip(err)?;
+				continue;
+			}
+		};
+		let input = zips.paths.len();
+		on_event(RezipEvent::Indexing {
+			from: &path,
+			files: zip.len(),
+		});
+		let mut names = HashSet::new();
+		for index in 0..zip.len() {
+			let file = match zip.by_index(index) {
+				Ok(file) => file,
+				Err(err) => {
+					skip(err.wrap_err(format!(
+						"Cannot read file[{}] in input ZIP archive {:?}",
+						index, path
+					)))?;
+					continue;
+				}
+			};
+			if no_dir_entries && file.is_dir() {
+				continue;
+			}
+			let size = file.size();
+			let excluded = min_size.is_some_and(|min_size| size < min_size)
+				|| max_size.is_some_and(|max_size| size > max_size);
+			if excluded && !file.is_dir() {
+				on_event(RezipEvent::Excluding {
+					name: file.name(),
+					from: &path,
+					bytes: size,
+				});
+				continue;
+			}
+			let name = if utf8_names {
+				file.utf8_name().unwrap_or_else(|| {
+					warn!(
+						"Keeping CP437 file name {:?} in input ZIP archive {:?}",
+						file.name(),
+						path
+					);
+					file.name()
+				})
+			} else {
+				file.name()
+			};
+			check_name(name, max_name_length, max_depth)
+				.wrap_err_with(|| format!("Invalid input ZIP archive {:?}", path))?;
+			let duplicate = !names.insert(name.as_os_str().to_owned());
+			if duplicate {
+				if !allow_duplicate_inputs {
+					return Err(eyre!(
+						"Duplicate file name {:?} in input ZIP archive {:?}",
+						name,
+						path
+					));
+				}
+				warn!(
+					"Keeping last of duplicate file name {:?} in input ZIP archive {:?}",
+					name, path
+				);
+			}
+			let normalized;
+			let name = if normalize_paths {
+				let original = name
+					.to_str()
+					.ok_or_else(|| eyre!("Invalid file name {:?}", name))?;
+				normalized = normalize_name(original, file.is_dir())
+					.ok_or_else(|| eyre!("Invalid file name {:?}", original))?;
+				Path::new(&normalized)
+			} else {
+				name
+			};
+			let name = match strip_components {
+				Some(count) => {
+					let original = name
+						.to_str()
+						.ok_or_else(|| eyre!("Invalid file name {:?}", name))?;
+					match strip_name(original, count) {
+						Some(stripped) => Path::new(stripped),
+						None => {
+							on_event(RezipEvent::Excluding {
+								name,
+								from: &path,
+								bytes: size,
+							});
+							continue;
+						}
+					}
+				}
+				None => name,
+			};
+			let name = match &file {
+				File::ZipFile(_) | File::TarFile(_) => match_glob_value(merge, name, match_options)
+					.map_or_else(
+						|| name.to_path_buf(),
+						|template| merge_name(&template, name).into(),
+					),
+				File::DirFile(_) => name.to_path_buf(),
+			};
+			let name = if rename.is_empty() {
+				name
+			} else {
+				let original = name
+					.to_str()
+					.ok_or_else(|| eyre!("Invalid file name {:?}", name))?;
+				let mut name = original.to_string();
+				for (regex, replacement) in rename {
+					name = regex.replace_all(&name, replacement.as_str()).into_owned();
+				}
+				if name.is_empty() || name.starts_with('/') {
+					return Err(eyre!(
+						"Invalid file name {:?} renamed from {:?}",
+						name,
+						original
+					));
+				}
+				PathBuf::from(name)
+			};
+			let name = match match_glob_value(prefix, &path, match_options) {
+				Some(prefix) => Path::new(&prefix).join(name),
+				None => name,
+			};
+			let name = if dedup_dirs && file.is_dir() {
+				dedup_dir(&mut dirs, &name)
+			} else {
+				name
+			};
+			if !no_dir_entries && !repack_only {
+				// Synthesizes missing directories, including the ones of prefix.
+				let mut ancestors = name.ancestors().skip(1).collect::<Vec<_>>();
+				ancestors.pop();
+				for directory in ancestors.into_iter().rev() {
+					let mut directory = directory.as_os_str().to_os_string();
+					directory.push("/");
+					let directory = if dedup_dirs {
+						dedup_dir(&mut dirs, Path::new(&directory))
+					} else {
+						PathBuf::from(directory)
+					};
+					files.entry(directory).or_default();
+				}
+			}
+			let files = files.entry(name).or_default();
+			if duplicate {
+				files.retain(|&(other, _index)| other != input);
+			}
+			if updated.is_some() {
+				files.retain(|&(other, _index)| Some(other) != updated);
+			}
+			files.push((input, index));
+		}
+		zips.push(path, zip);
+		timings.indexing.insert(input, indexed.elapsed());
+	}
+	Ok(files)
+}
+
+// Output ZIP archive being written with the state carried across its files.
+struct OutputState<'a> {
+	path: &'a Path,
+	zip: OutputZip,
+	length: Rc<Cell<u64>>,
+	temporary: TempPath,
+	force: bool,
+	comment: Vec<u8>,
+	trailer: Vec<u8>,
+	total_pad_length: u64,
+	volumes: usize,
+	volume_files: usize,
+	volume_directory_length: u64,
+	entries: usize,
+	digests: Vec<(String, PathBuf)>,
+	shapes: Vec<(PathBuf, Vec<usize>)>,
+	written: Vec<String>,
+	originals: HashMap<[u8; 32], &'a Path>,
+	addresses: HashSet<PathBuf>,
+	zero_files: Vec<TempPath>,
+	indices: HashMap<usize, i128>,
+	duplicate_files: usize,
+	duplicate_length: u64,
+	now: DateTime,
+	latest: Option<DateTime>,
+}
+
+impl<'a> OutputState<'a> {
+	fn new(
+		path: &'a Path,
+		(zip, length, temporary): (OutputZip, Rc<Cell<u64>>, TempPath),
+		force: bool,
+		comment: Vec<u8>,
+		trailer: Vec<u8>,
+	) -> Self {
+		Self {
+			path,
+			zip,
+			length,
+			temporary,
+			force,
+			comment,
+			trailer,
+			total_pad_length: 0,
+			volumes: 0,
+			volume_files: 0,
+			volume_directory_length: 0,
+			entries: 0,
+			digests: Vec::new(),
+			shapes: Vec::new(),
+			written: Vec::new(),
+			originals: HashMap::new(),
+			addresses: HashSet::new(),
+			zero_files: Vec::new(),
+			indices: HashMap::new(),
+			duplicate_files: 0,
+			duplicate_length: 0,
+			now: DateTime::try_from(OffsetDateTime::now_utc()).unwrap_or_default(),
+			latest: None,
+		}
+	}
+}
+
+// Writes the file of the given name merged or stacked from the given files of input ZIP archives.
+#[allow(clippy::too_many_arguments)]
+fn write_entry<'a>(
+	rezip: &Rezip,
+	parsed: &Parsed,
+	state: &mut OutputState<'a>,
+	(name, files): (&'a PathBuf, &'a Vec<(usize, usize)>),
+	stacking: &[usize],
+	inputs: &mut Vec<PathBuf>,
+	zips: &mut Inputs,
+	timings: &mut Timings,
+	mut on_event: impl FnMut(RezipEvent<'_>),
+) -> Result<()> {
+	let Rezip {
+		recompute_crc,
+		collision,
+		strict,
+		split_size,
+		checksums,
+		dedup,
+		content_address,
+		entry_timestamp_source,
+		keep_extra_fields,
+		owner,
+		group,
+		preserve_owner,
+		align_all,
+		stack_gzip,
+		stack_dtype,
+		npy_order,
+		stack_order,
+		on_missing_stack_member,
+		csv_header,
+		json_merge,
+		zstd_long,
+		max_level,
+		zstd_threads,
+		verify_output,
+		..
+	} = *rezip;
+	let Parsed {
+		automatic,
+		recompress,
+		precompress,
+		align,
+		stack_cmd,
+		stack,
+		cache_dir,
+		..
+	} = parsed;
+	let Parsed {
+		match_options,
+		aligned_compressed,
+		stack_shape,
+		zip64,
+		..
+	} = *parsed;
+	let (path, force, now) = (state.path, state.force, state.now);
+	let OutputState {
+		path: _,
+		zip,
+		length,
+		temporary,
+		force: _,
+		comment,
+		trailer: _,
+		total_pad_length,
+		volumes,
+		volume_files,
+		volume_directory_length,
+		entries,
+		digests,
+		shapes,
+		written,
+		originals,
+		addresses,
+		zero_files,
+		indices,
+		duplicate_files,
+		duplicate_length,
+		now: _,
+		latest,
+	} = state;
+	let timed = Instant::now();
+	if files.is_empty() {
+		if content_address {
+			return Ok(());
+		}
+		if zip64 == Zip64Mode::Never {
+			check_zip64(path, Some(name), *entries, length.get())?;
+		}
+		*entries += 1;
+		on_event(RezipEvent::AddingDirectory { name });
+		zip.add_directory(name.to_str().unwrap(), FileOptions::default())
+			.wrap_err_with(|| format!("Cannot add directory to output ZIP archive {:?}", path))?;
+		written.push(name.to_str().unwrap().to_owned());
+		return Ok(());
+	}
+	let format = Format::new(name);
+	let command = if files.len() > 1 {
+		match_glob_value(stack_cmd, name, match_options)
+	} else {
+		None
+	};
+	let axis = match format {
+		Some(Format::Npy | Format::NpyGz) => npy_axis(stack, name, match_options),
+		Some(_) if files.len() > 1 => match_glob_value(stack, name, match_options),
+		_ => None,
+	};
+	let missing = match axis {
+		Some(_axis) if command.is_none() && !stacking.is_empty() => stacking
+			.iter()
+			.copied()
+			.filter(|&input| files.iter().all(|&(other, _index)| other != input))
+			.collect(),
+		_ => Vec::new(),
+	};
+	let axis = axis.filter(|_axis| files.len() > 1 || !missing.is_empty());
+	let zeroed;
+	let (axis, files) = match on_missing_stack_member {
+		_ if axis.is_none() || missing.is_empty() => (axis, files),
+		Some(MissingPolicy::Error) => {
+			let paths = missing
+				.iter()
+				.map(|&input| &inputs[input])
+				.collect::<Vec<_>>();
+			return Err(eyre!("Cannot stack {:?} missing in {:?}", name, paths));
+		}
+		Some(MissingPolicy::Skip) => {
+			for input in missing.iter().copied() {
+				on_event(RezipEvent::Missing {
+					name,
+					from: &inputs[input],
+					zeroed: false,
+				});
+			}
+			(None, files)
+		}
+		Some(MissingPolicy::Zero) => {
+			let zeros = zero_npy(zips, files[0], name)?;
+			let zip = zips.open(&zeros)?;
+			let zero = zips.paths.len();
+			zips.push(zeros.to_path_buf(), zip);
+			inputs.push(zeros.to_path_buf());
+			zero_files.push(zeros);
+			for input in missing.iter().copied() {
+				on_event(RezipEvent::Missing {
+					name,
+					from: &inputs[input],
+					zeroed: true,
+				});
+			}
+			zeroed = stacking
+				.iter()
+				.map(|&input| {
+					files
+						.iter()
+						.copied()
+						.find(|&(other, _index)| other == input)
+						.unwrap_or((zero, 0))
+				})
+				.collect::<Vec<_>>();
+			(axis, &zeroed)
+		}
+		None => unreachable!(),
+	};
+	let stacked = axis.is_some() || command.is_some();
+	let gunzipped;
+	let (member, name) =
+		if command.is_none() && axis.is_some() && format == Some(Format::NpyGz) && !stack_gzip {
+			gunzipped = name.with_extension("");
+			(name, &gunzipped)
+		} else {
+			(name, name)
+		};
+	let sorted;
+	let files = match (stack_order, &rezip.stack_by_index) {
+		_ if !stacked => files,
+		(Some(order), _) => {
+			sorted = sort_files(zips, inputs, files, order)?;
+			&sorted
+		}
+		(None, Some(member)) => {
+			sorted = sort_files_by_index(zips, files, member, indices)?;
+			&sorted
+		}
+		(None, None) => files,
+	};
+	let (input, index) = if stacked {
+		files.last().copied().unwrap()
+	} else {
+		if strict && files.len() > 1 && collision.is_none() {
+			let paths = files
+				.iter()
+				.map(|&(input, _index)| &inputs[input])
+				.collect::<Vec<_>>();
+			return Err(eyre!(
+				"Cannot merge {:?} of {:?} without stacking in strict mode",
+				name,
+				paths
+			));
+		}
+		select_file(zips, files, collision.unwrap_or(CollisionPolicy::Last))?
+	};
+	let from = (!stacked).then(|| inputs[input].as_path());
+	let precompressed = match_input_glob_value(precompress, name, from, match_options);
+	let recompressed = match select_method(
+		zips,
+		(input, index),
+		(name, from),
+		recompress,
+		automatic,
+		match_options,
+	)? {
+		Some((algorithm, reason)) => {
+			let method = method_name(algorithm, None);
+			on_event(RezipEvent::Selecting {
+				name,
+				method,
+				reason,
+			});
+			Some((algorithm, None, None))
+		}
+		None => match_input_glob_value(recompress, name, from, match_options),
+	};
+	let last_modified = match entry_timestamp_source {
+		TimestampSource::Source => zips.get(input)?.by_index(index)?.last_modified(),
+		TimestampSource::Now => now,
+		TimestampSource::Fixed(time) => time,
+		TimestampSource::Max => {
+			let mut newest = None;
+			for (input, index) in files.iter().copied().filter(|_file| stacked) {
+				let time = zips.get(input)?.by_index(index)?.last_modified();
+				if newest.is_none_or(|newest| time_key(time) > time_key(newest)) {
+					newest = Some(time);
+				}
+			}
+			match newest {
+				Some(newest) => newest,
+				None => zips.get(input)?.by_index(index)?.last_modified(),
+			}
+		}
+	};
+	if latest.is_none_or(|latest| time_key(last_modified) > time_key(latest)) {
+		*latest = Some(last_modified);
+	}
+	let (is_dir, algorithm, level, work_factor, options, extra, ownership, precompressed) = {
+		let file = zips.get(input)?.by_index(index)?;
+		let is_dir = file.is_dir();
+		let (algorithm, level, work_factor) = match recompressed {
+			Some((algorithm, level, work_factor)) => (algorithm, level, work_factor),
+			None => (file.compression(), None, None),
+		};
+		let precompressed = match precompressed {
+			Some(level) => Some(Precompression::Xz(level)),
+			None if algorithm != CompressionMethod::Stored => aligned_compressed
+				.filter(|_level| match_input_glob_value(align, name, from, match_options).is_some())
+				.map(Precompression::Zstd),
+			None => None,
+		}
+		.filter(|_codec| !is_dir);
+		let (algorithm, level, work_factor) = if precompressed.is_some() {
+			(CompressionMethod::Stored, None, None)
+		} else {
+			(algorithm, level, work_factor)
+		};
+		let level = match level.zip(max_level) {
+			Some((level, max_level)) => match clamp_level(algorithm, level, max_level) {
+				Some(clamped) => {
+					let method = method_name(algorithm, Some(level));
+					on_event(RezipEvent::Clamping {
+						name,
+						method,
+						level: clamped,
+					});
+					Some(clamped)
+				}
+				None => Some(level),
+			},
+			None => level,
+		};
+		let options = FileOptions::default()
+			.compression_method(algorithm)
+			.last_modified_time(last_modified)
+			.large_file(zip64 == Zip64Mode::Always);
+		let options = options.compression_level(level);
+		let options = file
+			.unix_mode()
+			.map_or(options, |mode| options.unix_permissions(mode));
+		let extra = if keep_extra_fields && !is_dir {
+			kept_extra_fields(file.extra_data())
+		} else {
+			Vec::new()
+		};
+		let preserved = if preserve_owner { file.owner() } else { None };
+		let ownership = (!is_dir && (owner.is_some() || group.is_some() || preserved.is_some()))
+			.then(|| {
+				let (uid, gid) = preserved.unwrap_or((0, 0));
+				(owner.unwrap_or(uid), group.unwrap_or(gid))
+			});
+		(
+			is_dir,
+			algorithm,
+			level,
+			work_factor,
+			options,
+			extra,
+			ownership,
+			precompressed,
+		)
+	};
+	let bytes = if align_all || algorithm == CompressionMethod::Stored {
+		match_input_glob_value(align, name, from, match_options)
+	} else {
+		None
+	};
+	let extra = if stacked { Vec::new() } else { extra };
+	let extra = match ownership {
+		Some(ownership) => with_unix_owner(extra, ownership),
+		None => extra,
+	};
+	if let Some(dedup) = dedup.filter(|_dedup| !is_dir && !stacked) {
+		let mut file = zips.get(input)?.by_index(index)?;
+		let mut hasher = Sha256::new();
+		copy(&mut file, &mut hasher).wrap_err_with(|| format!("Cannot digest {:?}", name))?;
+		let digest: [u8; 32] = hasher.finalize().into();
+		if let Some(of) = originals.get(&digest) {
+			let dropped = dedup == DedupMode::Drop;
+			on_event(RezipEvent::Duplicate { name, of, dropped });
+			*duplicate_files += 1;
+			*duplicate_length += file.size();
+			if dropped {
+				return Ok(());
+			}
+		} else {
+			// Names of deduplicated files are never renamed as they are not stacked.
+			originals.insert(digest, member.as_path());
+		}
+	}
+	let stack_into =
+		|mut writer: &mut dyn Write, zips: &mut Inputs| match (command.as_deref(), axis, format) {
+			(Some(command), _axis, _format) => {
+				stack_command(path, &mut writer, zips, files, name, command)?;
+				Ok(None)
+			}
+			(None, Some(axis), Some(Format::NpyGz)) if stack_gzip => {
+				let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+				let shape = try_stack_npy(
+					path,
+					&mut encoder,
+					zips,
+					files,
+					member,
+					axis,
+					stack_shape,
+					npy_order,
+					stack_dtype,
+					&rezip.stack_cast,
+				)?;
+				encoder.finish().wrap_err_with(|| {
+					format!("Cannot write file to output ZIP archive {:?}", path)
+				})?;
+				Ok(Some(shape))
+			}
+			(None, Some(axis), Some(Format::Npy | Format::NpyGz)) => try_stack_npy(
+				path,
+				&mut writer,
+				zips,
+				files,
+				member,
+				axis,
+				stack_shape,
+				npy_order,
+				stack_dtype,
+				&rezip.stack_cast,
+			)
+			.map(Some),
+			(None, Some(axis), Some(Format::Csv)) => {
+				stack_csv(path, &mut writer, zips, files, name, axis, csv_header)?;
+				Ok(None)
+			}
+			(None, Some(axis), Some(Format::Json)) => {
+				stack_json(path, &mut writer, zips, files, name, axis, json_merge)?;
+				Ok(None)
+			}
+			#[cfg(feature = "parquet")]
+			(None, Some(axis), Some(Format::Parquet)) => {
+				try_stack_parquet(path, &mut writer, zips, files, name, axis)?;
+				Ok(None)
+			}
+			_ => unreachable!(),
+		};
+	let addressed;
+	let mut prestacked = None;
+	let name = if content_address {
+		if is_dir {
+			return Ok(());
+		}
+		let mut hasher = blake3::Hasher::new();
+		if stacked {
+			// Stacks once into a temporary file to be hashed and then copied.
+			let mut file = tempfile::tempfile()
+				.map(BufWriter::new)
+				.wrap_err("Cannot create temporary file")?;
+			let shape = stack_into(&mut file, zips)?;
+			let mut file = file
+				.into_inner()
+				.map_err(io::IntoInnerError::into_error)
+				.wrap_err("Cannot write temporary file")?;
+			file.rewind()
+				.and_then(|()| copy(&mut file, &mut hasher))
+				.and_then(|_length| file.rewind())
+				.wrap_err("Cannot read temporary file")?;
+			prestacked = Some((BufReader::new(file), shape));
+		} else {
+			let mut file = zips.get(input)?.by_index(index)?;
+			copy(&mut file, &mut hasher).wrap_err_with(|| format!("Cannot digest {:?}", name))?;
+		}
+		let mut address = hasher.finalize().to_hex().to_string();
+		if let Some(extension) = name.extension().and_then(OsStr::to_str) {
+			address.push('.');
+			address.push_str(extension);
+		}
+		addressed = PathBuf::from(address);
+		if !addresses.insert(addressed.clone()) {
+			on_event(RezipEvent::Duplicate {
+				name,
+				of: &addressed,
+				dropped: true,
+			});
+			*duplicate_files += 1;
+			*duplicate_length += hasher.count();
+			return Ok(());
+		}
+		on_event(RezipEvent::Addressing {
+			name,
+			to: &addressed,
+		});
+		&addressed
+	} else {
+		name
+	};
+	if let Some(split_size) = split_size {
+		let stacked = if stacked {
+			files.as_slice()
+		} else {
+			&[(input, index)]
+		};
+		let mut size = 0;
+		for (input, index) in stacked.iter().copied() {
+			let file = zips.get(input)?.by_index(index)?;
+			size += if file.compression() == algorithm {
+				file.compressed_size()
+			} else {
+				file.size()
+			};
+		}
+		let name_length = name.as_os_str().len() as u64;
+		let header_length =
+			30 + name_length + 20 + 4 + u64::from(bytes.unwrap_or(0)) + extra.len() as u64;
+		let directory_length = 46 + name_length + 28 + extra.len() as u64;
+		let end_length = 22 + 56 + 20;
+		let volume_length = length.get()
+			+ header_length
+			+ size + *volume_directory_length
+			+ directory_length
+			+ end_length;
+		if *volume_files > 0 && volume_length > split_size {
+			*volumes += 1;
+			let volume = path.with_extension(format!("z{:02}", volumes));
+			on_event(RezipEvent::Finishing {
+				path,
+				volume: Some(&volume),
+			});
+			if !force && volume.exists() {
+				return Err(eyre!("Cannot create existing volume {:?}", volume));
+			}
+			if zip64 == Zip64Mode::Never {
+				check_zip64(path, None, *entries, length.get())?;
+			}
+			let (next, next_length, next_temporary) =
+				create_output(path, rezip.tmpdir.as_deref(), force, None)?;
+			let temporary = mem::replace(temporary, next_temporary);
+			zip.set_raw_comment(comment.clone());
+			let verified = verify_output.then_some(shapes.as_slice());
+			finish_output(&volume, zip, temporary, force, verified, &[])?;
+			shapes.clear();
+			(*zip, *length) = (next, next_length);
+			*volume_files = 0;
+			*volume_directory_length = 0;
+			*entries = 0;
+		}
+		if header_length + size + directory_length + end_length > split_size {
+			warn!(
+				"{:?} exceeds split size of {} bytes on its own",
+				name, split_size
+			);
+		}
+		*volume_files += 1;
+		*volume_directory_length += directory_length;
+	}
+	if zip64 == Zip64Mode::Never {
+		check_zip64(path, Some(name), *entries, length.get())?;
+		if !stacked {
+			let file = zips.get(input)?.by_index(index)?;
+			if file.size().max(file.compressed_size()) > u64::from(u32::MAX) {
+				return Err(eyre!(
+					"Cannot write {:?} exceeding 4 GiB without ZIP64",
+					name
+				))
+				.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path));
+			}
+		}
+	}
+	*entries += 1;
+	if is_dir {
+		on_event(RezipEvent::MergingDirectory {
+			name,
+			from: &inputs[input],
+		});
+		zip.add_directory(name.to_str().unwrap(), options)
+			.wrap_err_with(|| format!("Cannot add directory to output ZIP archive {:?}", path))?;
+		written.push(name.to_str().unwrap().to_owned());
+		return Ok(());
+	}
+	// Copies lack ZIP64 extended information unless their sizes require it.
+	let copyable = zip64 == Zip64Mode::Never && extra.is_empty() && precompressed.is_none();
+	if copyable && !stacked && bytes.is_none() && level.is_none() {
+		// Copies the file as is unless its method or modification time changes.
+		let raw = zips.get(input)?.by_index_raw(index).is_some_and(|file| {
+			file.compression() == algorithm
+				&& time_key(file.last_modified()) == time_key(last_modified)
+		});
+		if raw {
+			if checksums.is_some() || recompute_crc {
+				let mut file = zips.get(input)?.by_index(index)?;
+				let mut checksum = Checksum::new(io::sink(), checksums);
+				copy(&mut file, &mut checksum)
+					.wrap_err_with(|| format!("Cannot verify {:?} in {:?}", name, inputs[input]))?;
+				let (digest, _length) = checksum.finalize();
+				digests.extend(digest.map(|digest| (digest, name.clone())));
+			}
+			let file = zips.get(input)?.by_index_raw(index).unwrap();
+			let bytes = file.size();
+			on_event(RezipEvent::Copying {
+				name,
+				method: method_name(algorithm, None),
+				from: &inputs[input],
+			});
+			zip.raw_copy_file_rename(file, name.to_str().unwrap())
+				.wrap_err_with(|| format!("Cannot copy file to output ZIP archive {:?}", path))?;
+			on_event(RezipEvent::Written { name, bytes });
+			timings.written(input, stacked, timed.elapsed(), bytes);
+			written.push(name.to_str().unwrap().to_owned());
+			return Ok(());
+		}
+	}
+	let cacheable = !stacked
+		&& bytes.is_none()
+		&& extra.is_empty()
+		&& precompressed.is_none()
+		&& work_factor.is_none()
+		&& algorithm != CompressionMethod::Stored
+		&& !(algorithm == CompressionMethod::Zstd && (zstd_long.is_some() || zstd_threads > 0));
+	if let Some(cache_dir) = cache_dir.as_deref().filter(|_dir| cacheable) {
+		let method = method_name(algorithm, level);
+		let mut hasher = blake3::Hasher::new();
+		hasher.update(method.as_bytes());
+		hasher.update(&[0]);
+		let mut file = zips.get(input)?.by_index(index)?;
+		let mut checksum = Checksum::new(&mut hasher, checksums);
+		copy(&mut file, &mut checksum).wrap_err_with(|| format!("Cannot digest {:?}", name))?;
+		let (digest, _length) = checksum.finalize();
+		digests.extend(digest.map(|digest| (digest, name.clone())));
+		drop(file);
+		let cached = cache_dir.join(format!("{}.zip", hasher.finalize().to_hex()));
+		let reused = cached.is_file();
+		on_event(RezipEvent::Caching {
+			name,
+			method,
+			reused,
+		});
+		if reused {
+			OpenOptions::new()
+				.write(true)
+				.open(&cached)
+				.and_then(|file| file.set_modified(SystemTime::now()))
+				.wrap_err_with(|| format!("Cannot touch cached file {:?}", cached))?;
+		} else {
+			let file = zips.get(input)?.by_index(index)?;
+			store_cached(&cached, file, algorithm, level)
+				.wrap_err_with(|| format!("Cannot write cached file {:?}", cached))?;
+		}
+		let unix_mode = zips.get(input)?.by_index(index)?.unix_mode();
+		let mut rewrapped = rewrap_cached(&cached, last_modified, unix_mode)
+			.wrap_err_with(|| format!("Cannot read cached file {:?}", cached))?;
+		let file = rewrapped
+			.by_index_raw(0)
+			.wrap_err("Cannot read temporary file")?;
+		let bytes = file.size();
+		zip.raw_copy_file_rename(file, name.to_str().unwrap())
+			.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+		on_event(RezipEvent::Written { name, bytes });
+		timings.written(input, stacked, timed.elapsed(), bytes);
+		written.push(name.to_str().unwrap().to_owned());
+		return Ok(());
+	}
+	let mut encoded = None;
+	if algorithm == CompressionMethod::Zstd && (zstd_long.is_some() || zstd_threads > 0) {
+		on_event(RezipEvent::Recompressing {
+			name,
+			method: method_name(algorithm, level),
+		});
+		let level = level.unwrap_or(3);
+		encoded = EncodedFile::zstd(name.to_str().unwrap(), level, zstd_long, zstd_threads)
+			.map(Some)
+			.wrap_err("Cannot create temporary file")?;
+	} else if let Some(work_factor) = work_factor {
+		on_event(RezipEvent::Recompressing {
+			name,
+			method: method_name(algorithm, level),
+		});
+		let level = level.map_or(6, i32::unsigned_abs);
+		encoded = EncodedFile::bzip2(name.to_str().unwrap(), level, work_factor)
+			.map(Some)
+			.wrap_err("Cannot create temporary file")?;
+	} else if let Some(bytes) = bytes {
+		on_event(RezipEvent::Aligning { name, bytes });
+		let pad_length = if extra.is_empty() {
+			zip.start_file_aligned(name.to_str().unwrap(), options, bytes)
+		} else {
+			start_file_with_extra_data(zip, name.to_str().unwrap(), options, &extra, bytes)
+		};
+		let pad_length = pad_length
+			.wrap_err_with(|| format!("Cannot start file in output ZIP archive {:?}", path))?;
+		on_event(RezipEvent::Padding {
+			name,
+			bytes: pad_length,
+		});
+		*total_pad_length += pad_length;
+	} else {
+		on_event(RezipEvent::Recompressing {
+			name,
+			method: method_name(algorithm, level),
+		});
+		if extra.is_empty() {
+			zip.start_file(name.to_str().unwrap(), options)
+		} else {
+			start_file_with_extra_data(zip, name.to_str().unwrap(), options, &extra, 1).map(drop)
+		}
+		.wrap_err_with(|| format!("Cannot start file in output ZIP archive {:?}", path))?;
+	}
+	let writer: &mut dyn Write = match &mut encoded {
+		Some(encoded) => encoded,
+		None => &mut *zip,
+	};
+	let checksum = Checksum::new(writer, checksums);
+	let mut writer = Precompressor::new(checksum, precompressed)
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	if stacked {
+		on_event(RezipEvent::Stacking {
+			name,
+			files: files.len(),
+		});
+		for (input, _index) in files.iter().copied() {
+			on_event(RezipEvent::StackingFrom {
+				name,
+				from: &inputs[input],
+			});
+		}
+		let shape = match prestacked {
+			Some((mut file, shape)) => {
+				copy(&mut file, &mut writer).wrap_err_with(|| {
+					format!("Cannot write file to output ZIP archive {:?}", path)
+				})?;
+				shape
+			}
+			None => stack_into(&mut writer, zips)?,
+		};
+		if let Some(shape) = shape {
+			shapes.push((name.clone(), shape));
+		}
+	} else {
+		let file = &mut zips.get(input)?.by_index(index)?;
+		on_event(RezipEvent::Merging {
+			name,
+			from: &inputs[input],
+		});
+		copy(file, &mut writer)
+			.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	}
+	let checksum = writer
+		.finish()
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	let (digest, bytes) = checksum.finalize();
+	digests.extend(digest.map(|digest| (digest, name.clone())));
+	if let Some(encoded) = encoded {
+		let unix_mode = zips.get(input)?.by_index(index)?.unix_mode();
+		let mut encoded = encoded
+			.finish(last_modified, unix_mode)
+			.wrap_err("Cannot write temporary file")?;
+		let file = encoded
+			.by_index_raw(0)
+			.wrap_err("Cannot read temporary file")?;
+		zip.raw_copy_file_rename(file, name.to_str().unwrap())
+			.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	}
+	on_event(RezipEvent::Written { name, bytes });
+	timings.written(input, stacked, timed.elapsed(), bytes);
+	written.push(name.to_str().unwrap().to_owned());
+	Ok(())
+}
+
+// Finishes the output ZIP archive and reports its padding, duplicates, changes, and checksums.
+fn finish_writing(
+	rezip: &Rezip,
+	parsed: &Parsed,
+	state: OutputState,
+	previous: Option<IndexMap<PathBuf, [u8; 32]>>,
+	mut on_event: impl FnMut(RezipEvent<'_>),
+) -> Result<()> {
+	let Rezip {
+		sfx,
+		touch_output,
+		verify_output,
+		checksums,
+		dedup,
+		cache_size,
+		..
+	} = *rezip;
+	let zip64 = parsed.zip64;
+	let OutputState {
+		path,
+		mut zip,
+		length,
+		temporary,
+		force,
+		comment,
+		trailer,
+		total_pad_length,
+		mut entries,
+		digests,
+		shapes,
+		mut written,
+		duplicate_files,
+		duplicate_length,
+		latest,
+		..
+	} = state;
+	if let Some(index) = &rezip.index_entry {
+		let name = index.as_path();
+		if written.iter().any(|file| file == name.to_str().unwrap()) {
+			return Err(eyre!(
+				"Cannot write index entry {:?} named like a file",
+				name
+			));
+		}
+		if zip64 == Zip64Mode::Never {
+			check_zip64(path, Some(name), entries, length.get())?;
+		}
+		entries += 1;
+		written.sort_unstable();
+		let mut list = written.join("\n");
+		if !list.is_empty() {
+			list.push('\n');
+		}
+		let options = FileOptions::default()
+			.compression_method(CompressionMethod::Stored)
+			.large_file(zip64 == Zip64Mode::Always);
+		zip.start_file(name.to_str().unwrap(), options)
+			.and_then(|()| zip.write_all(list.as_bytes()).map_err(From::from))
+			.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+		on_event(RezipEvent::Written {
+			name,
+			bytes: list.len() as u64,
+		});
+	}
+	if zip64 == Zip64Mode::Never {
+		check_zip64(path, None, entries, length.get())?;
+	}
+	on_event(RezipEvent::Finishing { path, volume: None });
+	zip.set_raw_comment(comment);
+	let verified = verify_output.then_some(shapes.as_slice());
+	finish_output(path, &mut zip, temporary, force, verified, &trailer)?;
+	if let Some(cache_dir) = &parsed.cache_dir {
+		evict_cached(cache_dir, cache_size)
+			.wrap_err_with(|| format!("Cannot evict cached files of {:?}", cache_dir))?;
+	}
+	#[cfg(unix)]
+	if sfx {
+		use std::os::unix::fs::PermissionsExt;
+		let mut permissions = fs::metadata(path)
+			.wrap_err_with(|| format!("Cannot get metadata of {:?}", path))?
+			.permissions();
+		permissions.set_mode(permissions.mode() | 0o100);
+		fs::set_permissions(path, permissions)
+			.wrap_err_with(|| format!("Cannot make {:?} executable", path))?;
+	}
+	let touched = match touch_output {
+		Some(TouchOutput::Now) => Some(SystemTime::now()),
+		Some(TouchOutput::Source) => latest
+			.and_then(|latest| latest.to_time().ok())
+			.map(SystemTime::from),
+		Some(TouchOutput::Fixed(time)) => Some(time.into()),
+		None => None,
+	};
+	if let Some(time) = touched {
+		OpenOptions::new()
+			.write(true)
+			.open(path)
+			.and_then(|file| file.set_modified(time))
+			.wrap_err_with(|| format!("Cannot set modification time of {:?}", path))?;
+	}
+	on_event(RezipEvent::TotalPadding {
+		path,
+		bytes: total_pad_length,
+	});
+	if let Some(dedup) = dedup {
+		on_event(RezipEvent::TotalDuplicates {
+			path,
+			files: duplicate_files,
+			bytes: duplicate_length,
+			dropped: dedup == DedupMode::Drop,
+		});
+	}
+	if let Some((against, previous)) = rezip.diff_against.as_deref().zip(previous) {
+		let current = digest_files(path)
+			.wrap_err_with(|| format!("Cannot read output ZIP archive {:?}", path))?;
+		let (mut added, mut modified, mut unchanged) = (0, 0, 0);
+		for (name, digest) in &current {
+			let change = match previous.get(name) {
+				None => {
+					added += 1;
+					"added"
+				}
+				Some(other) if other != digest => {
+					modified += 1;
+					"modified"
+				}
+				Some(_other) => {
+					unchanged += 1;
+					"unchanged"
+				}
+			};
+			on_event(RezipEvent::Changed { name, change });
+		}
+		let mut removed = 0;
+		for name in previous.keys().filter(|name| !current.contains_key(*name)) {
+			removed += 1;
+			on_event(RezipEvent::Changed {
+				name,
+				change: "removed",
+			});
+		}
+		on_event(RezipEvent::TotalChanges {
+			path,
+			against,
+			added,
+			removed,
+			modified,
+			unchanged,
+		});
+	}
+	if let Some(checksums) = checksums {
+		let mut sums = path.as_os_str().to_owned();
+		sums.push(".");
+		sums.push(checksums.extension());
+		let sums = PathBuf::from(sums);
+		let mut file = OpenOptions::new()
+			.create_new(!force)
+			.create(true)
+			.truncate(true)
+			.write(true)
+			.open(&sums)
+			.map(BufWriter::new)
+			.wrap_err_with(|| format!("Cannot create checksum file {:?}", sums))?;
+		for (digest, name) in digests {
+			writeln!(file, "{}  {}", digest, name.display())
+				.wrap_err_with(|| format!("Cannot write checksum file {:?}", sums))?;
+		}
+		file.flush()
+			.wrap_err_with(|| format!("Cannot write checksum file {:?}", sums))?;
+	}
+	Ok(())
+}
+
+// Matches the stack axis of NPY files by their names with and without extensions.
+fn npy_axis(stack: &[(Glob, Option<usize>)], name: &Path, options: GlobOptions) -> Option<usize> {
+	match Format::new(name) {
+		Some(Format::Npy) => {
+			// Matches NPZ members by their array names as well.
+			let key = name.with_extension("");
+			match_glob_values(stack, &[name, &key], None, options)
+		}
+		Some(Format::NpyGz) => {
+			let npy = name.with_extension("");
+			let key = npy.with_extension("");
+			match_glob_values(stack, &[name, &npy, &key], None, options)
+		}
+		_ => None,
+	}
+}
+
+// Reports the time spent on indexing and compressing each input ZIP archive and in total.
+fn report_timings(
+	path: &Path,
+	inputs: &[PathBuf],
+	timings: &Timings,
+	started: Instant,
+	mut on_event: impl FnMut(RezipEvent<'_>),
+) {
+	let mut total = (Duration::ZERO, Duration::ZERO, 0);
+	for (input, from) in inputs.iter().enumerate() {
+		let from = Some(from.as_path());
+		if let Some(&elapsed) = timings.indexing.get(&input) {
+			total.0 += elapsed;
+			on_event(RezipEvent::Timing {
+				path,
+				phase: "indexing",
+				from,
+				seconds: elapsed.as_secs_f64(),
+				bytes: 0,
+			});
+		}
+		if let Some(&(elapsed, bytes)) = timings.compressing.get(&input) {
+			total.1 += elapsed;
+			total.2 += bytes;
+			on_event(RezipEvent::Timing {
+				path,
+				phase: "compressing",
+				from,
+				seconds: elapsed.as_secs_f64(),
+				bytes,
+			});
+		}
+	}
+	let (stacking, stacked) = timings.stacking;
+	for (phase, elapsed, bytes) in [
+		("indexing", total.0, 0),
+		("compressing", total.1, total.2),
+		("stacking", stacking, stacked),
+		("total", started.elapsed(), total.2 + stacked),
+	] {
+		on_event(RezipEvent::Timing {
+			path,
+			phase,
+			from: None,
+			seconds: elapsed.as_secs_f64(),
+			bytes,
+		});
+	}
+}
+
+// Reports the compression method of each file of the input ZIP archives with a summary.
+fn list_methods(
+	inputs: &[PathBuf],
+	zips: &mut Inputs,
+	mut on_event: impl FnMut(RezipEvent<'_>),
+) -> Result<()> {
+	let mut methods = IndexMap::<_, usize>::new();
+	for (input, path) in inputs.iter().enumerate() {
+		let zip = zips.get(input)?;
+		for index in 0..zip.len() {
+			let file = zip.by_index(index).wrap_err_with(|| {
+				format!(
+					"Cannot read file[{}] in input ZIP archive {:?}",
+					index, path
+				)
+			})?;
+			if file.is_dir() {
+				continue;
+			}
+			let (name, algorithm) = (file.name().to_path_buf(), file.compression());
+			drop(file);
+			let mut header = Vec::new();
+			if let Some(file) = zip.by_index_raw(index) {
+				file.take(18)
+					.read_to_end(&mut header)
+					.wrap_err_with(|| format!("Cannot read {:?}", name))?;
+			}
+			let method = infer_method(algorithm, &header);
+			*methods.entry(method.clone()).or_default() += 1;
+			on_event(RezipEvent::Reported {
+				name: &name,
+				method,
+				from: path,
+			});
+		}
+	}
+	methods.sort_by(|_method, count, _other, other_count| other_count.cmp(count));
+	println!("{:>10}  Method", "Files");
+	for (method, count) in &methods {
+		println!("{:>10}  {}", count, method);
+	}
+	let total = methods.values().sum::<usize>();
+	let plural = if methods.len() == 1 { "" } else { "s" };
+	println!("{:>10}  {} method{}", total, methods.len(), plural);
+	Ok(())
+}
+
+// Lists the files of the input ZIP archives in the format of unzip.
+fn list_files(rezip: &Rezip, parsed: &Parsed, inputs: &[PathBuf], zips: &mut Inputs) -> Result<()> {
+	let (align_all, align, match_options) = (rezip.align_all, &parsed.align, parsed.match_options);
+	for (input, path) in inputs.iter().enumerate() {
+		let zip = zips.get(input)?;
+		println!("{:?}:", path);
+		println!(
+			"{:>10}  {:<9}  {:>10}  {:>4}  {:<16}  {:<8}  {:>5}  Name",
+			"Length", "Method", "Size", "Cmpr", "Date & Time", "CRC-32", "Align"
+		);
+		let (mut total_length, mut total_size) = (0, 0);
+		for index in 0..zip.len() {
+			let file = zip.by_index(index).wrap_err_with(|| {
+				format!(
+					"Cannot read file[{}] in input ZIP archive {:?}",
+					index, path
+				)
+			})?;
+			let name = file.name();
+			let (length, size) = (file.size(), file.compressed_size());
+			let ratio = (size.min(length) * 100)
+				.checked_div(length)
+				.map_or(0, |ratio| 100 - ratio);
+			let time = file.last_modified();
+			let crc32 = file
+				.crc32()
+				.map_or("-".into(), |crc32| format!("{:08x}", crc32));
+			let bytes = if file.is_dir() {
+				None
+			} else if align_all || file.compression() == CompressionMethod::Stored {
+				match_input_glob_value(align, name, Some(path), match_options)
+			} else {
+				None
+			};
+			let align = file
+				.data_start()
+				.zip(bytes)
+				.map_or("-".into(), |(data_start, bytes)| {
+					(data_start % u64::from(bytes)).to_string()
+				});
+			println!(
+				"{:>10}  {:<9}  {:>10}  {:>3}%  {:04}-{:02}-{:02} {:02}:{:02}  {:<8}  {:>5}  {}",
+				length,
+				method_name(file.compression(), None),
+				size,
+				ratio,
+				time.year(),
+				time.month(),
+				time.day(),
+				time.hour(),
+				time.minute(),
+				crc32,
+				align,
+				name.display()
+			);
+			total_length += length;
+			total_size += size;
+		}
+		let plural = if zip.len() == 1 { "" } else { "s" };
+		println!(
+			"{:>10}  {:<9}  {:>10}  {:>4}  {:<16}  {:<8}  {:>5}  {} file{}",
+			total_length,
+			"",
+			total_size,
+			"",
+			"",
+			"",
+			"",
+			zip.len(),
+			plural
+		);
+	}
+	Ok(())
+}
+
+// Lists the files of the input ZIP archives as JSON array.
+fn list_files_json(
+	rezip: &Rezip,
+	parsed: &Parsed,
+	inputs: &[PathBuf],
+	zips: &mut Inputs,
+) -> Result<()> {
+	let (align_all, align, match_options) = (rezip.align_all, &parsed.align, parsed.match_options);
+	let mut listing = Vec::new();
+	for (input, path) in inputs.iter().enumerate() {
+		let zip = zips.get(input)?;
+		for index in 0..zip.len() {
+			let file = zip.by_index(index).wrap_err_with(|| {
+				format!(
+					"Cannot read file[{}] in input ZIP archive {:?}",
+					index, path
+				)
+			})?;
+			let name = file.name();
+			let time = file.last_modified();
+			let bytes = if file.is_dir() {
+				None
+			} else if align_all || file.compression() == CompressionMethod::Stored {
+				match_input_glob_value(align, name, Some(path), match_options)
+			} else {
+				None
+			};
+			let alignment = file
+				.data_start()
+				.zip(bytes)
+				.map(|(data_start, bytes)| data_start % u64::from(bytes));
+			listing.push(json!({
+				"name": name.to_string_lossy(),
+				"from": path.to_string_lossy(),
+				"method": method_name(file.compression(), None),
+				"compressed_size": json_u64(file.compressed_size()),
+				"uncompressed_size": json_u64(file.size()),
+				"crc32": file.crc32().map(|crc32| format!("{:08x}", crc32)),
+				"modified": format!(
+					"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+					time.year(),
+					time.month(),
+					time.day(),
+					time.hour(),
+					time.minute(),
+					time.second()
+				),
+				"is_dir": file.is_dir(),
+				"unix_mode": file.unix_mode(),
+				"data_start": file.data_start().map(json_u64),
+				"alignment": alignment,
+			}));
+		}
+	}
+	println!("{}", Value::Array(listing));
+	Ok(())
+}
+
+// Checks whether the files of the input ZIP archives are compressed and aligned as requested.
+fn check_files(
+	rezip: &Rezip,
+	parsed: &Parsed,
+	files: &IndexMap<PathBuf, Vec<(usize, usize)>>,
+	inputs: &[PathBuf],
+	zips: &mut Inputs,
+	mut on_event: impl FnMut(RezipEvent<'_>),
+) -> Result<()> {
+	let Rezip {
+		verify,
+		align_all,
+		zstd_long,
+		max_level,
+		..
+	} = *rezip;
+	let Parsed {
+		automatic,
+		recompress,
+		align,
+		..
+	} = parsed;
+	let match_options = parsed.match_options;
+	let mut compressed = true;
+	let mut aligned = true;
+	let mut corrupted = 0;
+	for (name, files) in files {
+		for (input, index) in files.iter().copied() {
+			if zips.get(input)?.by_index(index)?.is_dir() {
+				continue;
+			}
+			if verify {
+				let mut file = zips.get(input)?.by_index(index)?;
+				if let Err(err) = copy(&mut file, &mut io::sink()) {
+					on_event(RezipEvent::Corrupted {
+						name,
+						from: &inputs[input],
+						error: err.to_string(),
+					});
+					corrupted += 1;
+				}
+			}
+			let recompressed = match select_method(
+				zips,
+				(input, index),
+				(name, Some(&inputs[input])),
+				recompress,
+				automatic,
+				match_options,
+			)? {
+				Some((algorithm, reason)) => {
+					let method = method_name(algorithm, None);
+					on_event(RezipEvent::Selecting {
+						name,
+						method,
+						reason,
+					});
+					Some((algorithm, None, None))
+				}
+				None => {
+					match_input_glob_value(recompress, name, Some(&inputs[input]), match_options)
+				}
+			};
+			let file = zips.get(input)?.by_index(index)?;
+			let (algorithm, level, recompress) = match recompressed {
+				Some((algorithm, level, _work_factor)) => {
+					(algorithm, level, file.compression() != algorithm)
+				}
+				None => (file.compression(), None, false),
+			};
+			let level = match level.zip(max_level) {
+				Some((level, max_level)) => match clamp_level(algorithm, level, max_level) {
+					Some(clamped) => {
+						let method = method_name(algorithm, Some(level));
+						on_event(RezipEvent::Clamping {
+							name,
+							method,
+							level: clamped,
+						});
+						Some(clamped)
+					}
+					None => Some(level),
+				},
+				None => level,
+			};
+			drop(file);
+			let window_log = zstd_long.filter(|_| algorithm == CompressionMethod::Zstd);
+			let recompress = if !recompress && (level.is_some() || window_log.is_some()) {
+				let mut header = Vec::new();
+				if let Some(file) = zips.get(input)?.by_index_raw(index) {
+					file.take(18)
+						.read_to_end(&mut header)
+						.wrap_err_with(|| format!("Cannot read {:?}", name))?;
+				}
+				check_level(algorithm, level, window_log, &header) == Some(false)
+			} else {
+				recompress
+			};
+			let file = zips.get(input)?.by_index(index)?;
+			on_event(RezipEvent::Compressed {
+				name,
+				method: method_name(algorithm, level),
+				from: &inputs[input],
+				requested: !recompress,
+			});
+			if recompress {
+				compressed = false;
+				continue;
+			}
+			let bytes = if align_all || algorithm == CompressionMethod::Stored {
+				match_input_glob_value(align, name, Some(&inputs[input]), match_options)
+			} else {
+				None
+			};
+			if let Some((data_start, bytes)) = file.data_start().zip(bytes) {
+				let requested = data_start % bytes as u64 == 0;
+				on_event(RezipEvent::Aligned {
+					name,
+					bytes,
+					from: &inputs[input],
+					requested,
+				});
+				if !requested {
+					aligned = false;
+				}
+			}
+		}
+	}
+	if corrupted > 0 {
+		let plural = if corrupted > 1 { "s" } else { "" };
+		return Err(eyre!("Cannot decompress {} file{}", corrupted, plural));
+	}
+	match (compressed, aligned) {
+		(true, true) => {
+			on_event(RezipEvent::Checked);
+			Ok(())
+		}
+		(false, true) => Err(CheckError::NotCompressed.into()),
+		(true, false) => Err(CheckError::NotAligned.into()),
+		(false, false) => Err(CheckError::NeitherCompressedNorAligned.into()),
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+	Npy,
+	NpyGz,
+	Csv,
+	Json,
+	#[cfg(feature = "parquet")]
+	Parquet,
+}
+
+impl Format {
+	fn new(name: &Path) -> Option<Self> {
+		let extension = name.extension().and_then(OsStr::to_str)?;
+		let stem = Path::new(name.file_stem()?);
+		if extension.eq_ignore_ascii_case("gz") {
+			Self::new(stem)
+				.filter(|&format| format == Self::Npy)
+				.map(|_format| Self::NpyGz)
+		} else if extension.eq_ignore_ascii_case("npy") {
+			Some(Self::Npy)
+		} else if extension.eq_ignore_ascii_case("csv") {
+			Some(Self::Csv)
+		} else if extension.eq_ignore_ascii_case("json") {
+			Some(Self::Json)
+		} else {
+			#[cfg(feature = "parquet")]
+			if extension.eq_ignore_ascii_case("parquet") {
+				return Some(Self::Parquet);
+			}
+			None
+		}
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_stack_npy<W>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+	reshape: StackShape,
+	order: NpyOrder,
+	dtype: Option<StackDtype>,
+	casts: &[(StackDtype, StackDtype)],
+) -> Result<Vec<usize>>
+where
+	W: Write,
+{
+	let (dtype, sources) = match dtype {
+		Some(dtype) => (Some(dtype), None),
+		None if !casts.is_empty() => match cast_dtype(zips, files, name, casts)
+			.wrap_err_with(|| format!("Cannot stack {:?}", name))?
+		{
+			Some((dtype, sources)) => (Some(dtype), Some(sources)),
+			None => (None, None),
+		},
+		None => (None, None),
+	};
+	let sources = sources.as_deref();
+	if let Some(dtype) = dtype {
+		return match dtype {
+			StackDtype::Bool => stack_npy_as::<bool, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[(StackDtype::Bool, cast_npy::<bool, _>)],
+			),
+			StackDtype::U8 => stack_npy_as::<u8, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::I8 => stack_npy_as::<i8, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::I8, cast_npy::<i8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::U16 => stack_npy_as::<u16, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::U16, cast_npy::<u16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::I16 => stack_npy_as::<i16, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::I16, cast_npy::<i16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::I8, cast_npy::<i8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::U32 => stack_npy_as::<u32, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::U32, cast_npy::<u32, _>),
+					(StackDtype::U16, cast_npy::<u16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::I32 => stack_npy_as::<i32, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::I32, cast_npy::<i32, _>),
+					(StackDtype::U16, cast_npy::<u16, _>),
+					(StackDtype::I16, cast_npy::<i16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::I8, cast_npy::<i8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::U64 => stack_npy_as::<u64, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::U64, cast_npy::<u64, _>),
+					(StackDtype::U32, cast_npy::<u32, _>),
+					(StackDtype::U16, cast_npy::<u16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::I64 => stack_npy_as::<i64, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::I64, cast_npy::<i64, _>),
+					(StackDtype::U32, cast_npy::<u32, _>),
+					(StackDtype::I32, cast_npy::<i32, _>),
+					(StackDtype::U16, cast_npy::<u16, _>),
+					(StackDtype::I16, cast_npy::<i16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::I8, cast_npy::<i8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			#[cfg(feature = "half")]
+			StackDtype::F16 => stack_npy_f16(path, zip, zips, files, name, axis, reshape, order)?
+				.ok_or_else(|| eyre!("Unsupported data-type"))
+				.wrap_err_with(|| format!("Cannot stack {:?}", name)),
+			StackDtype::F32 => stack_npy_as::<f32, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::F32, cast_npy::<f32, _>),
+					#[cfg(feature = "half")]
+					(StackDtype::F16, cast_npy::<F16, _>),
+					(StackDtype::U16, cast_npy::<u16, _>),
+					(StackDtype::I16, cast_npy::<i16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::I8, cast_npy::<i8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+			StackDtype::F64 => stack_npy_as::<f64, W>(
+				path,
+				zip,
+				zips,
+				files,
+				name,
+				axis,
+				reshape,
+				order,
+				sources,
+				&[
+					(StackDtype::F64, cast_npy::<f64, _>),
+					(StackDtype::F32, cast_npy::<f32, _>),
+					#[cfg(feature = "half")]
+					(StackDtype::F16, cast_npy::<F16, _>),
+					(StackDtype::U32, cast_npy::<u32, _>),
+					(StackDtype::I32, cast_npy::<i32, _>),
+					(StackDtype::U16, cast_npy::<u16, _>),
+					(StackDtype::I16, cast_npy::<i16, _>),
+					(StackDtype::U8, cast_npy::<u8, _>),
+					(StackDtype::I8, cast_npy::<i8, _>),
+					(StackDtype::Bool, cast_npy::<bool, _>),
+				],
+			),
+		};
+	}
+	if let Some(shape) = stack_npy::<f64, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<f32, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<i64, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<u64, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<i32, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<u32, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<i16, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<u16, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<i8, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<u8, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	if let Some(shape) = stack_npy::<bool, W>(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	#[cfg(feature = "half")]
+	if let Some(shape) = stack_npy_f16(path, zip, zips, files, name, axis, reshape, order)? {
+		return Ok(shape);
+	}
+	Err(eyre!("Unsupported data-type")).wrap_err_with(|| format!("Cannot stack {:?}", name))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn stack_npy<A, W>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+	reshape: StackShape,
+	order: NpyOrder,
+) -> Result<Option<Vec<usize>>>
+where
+	A: ReadableElement + WritableElement + Copy,
+	W: Write,
+{
+	let array =
+		read_stack_npy(zips, files, name, axis, reshape, order, |file, _from| {
+			match ArrayD::<A>::read_npy(file) {
+				Ok(array) => Ok(Some(array)),
+				Err(ReadNpyError::WrongDescriptor(_)) => Ok(None),
+				Err(err) => Err(err.into()),
+			}
+		})
+		.wrap_err_with(|| format!("Cannot stack {:?}", name))?;
+	let Some(array) = array else {
+		return Ok(None);
+	};
+	array
+		.write_npy(zip)
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	Ok(Some(array.shape().to_vec()))
+}
+
+type CastNpy<A> = fn(&[u8]) -> Result<Option<ArrayD<A>>, ReadNpyError>;
+
+// Casts the array losslessly from the data-type `S` unless it is of another one.
+fn cast_npy<S, A>(npy: &[u8]) -> Result<Option<ArrayD<A>>, ReadNpyError>
+where
+	S: ReadableElement + Copy,
+	A: From<S>,
+{
+	match ArrayD::<S>::read_npy(npy) {
+		Ok(array) => Ok(Some(array.mapv(A::from))),
+		Err(ReadNpyError::WrongDescriptor(_)) => Ok(None),
+		Err(err) => Err(err),
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn stack_npy_as<A, W>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+	reshape: StackShape,
+	order: NpyOrder,
+	sources: Option<&[StackDtype]>,
+	casts: &[(StackDtype, CastNpy<A>)],
+) -> Result<Vec<usize>>
+where
+	A: WritableElement + Clone,
+	W: Write,
+{
+	let array = read_stack_npy(zips, files, name, axis, reshape, order, |file, from| {
+		let mut npy = Vec::new();
+		file.read_to_end(&mut npy)?;
+		let casts = casts
+			.iter()
+			.filter(|(source, _cast)| sources.is_none_or(|sources| sources.contains(source)));
+		for (_source, cast) in casts {
+			if let Some(array) = cast(&npy)? {
+				return Ok(Some(array));
+			}
+		}
+		Err(eyre!("Cannot cast data-type of {:?} losslessly", from))
+	})
+	.and_then(|array| array.ok_or_else(|| eyre!("Unsupported data-type")))
+	.wrap_err_with(|| format!("Cannot stack {:?}", name))?;
+	array
+		.write_npy(zip)
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	Ok(array.shape().to_vec())
+}
+
+#[cfg(feature = "half")]
+#[allow(clippy::too_many_arguments)]
+fn stack_npy_f16<W>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+	reshape: StackShape,
+	order: NpyOrder,
+) -> Result<Option<Vec<usize>>>
+where
+	W: Write,
+{
+	let array =
+		read_stack_npy(zips, files, name, axis, reshape, order, |file, _from| {
+			match ArrayD::<F16>::read_npy(file) {
+				Ok(array) => Ok(Some(array)),
+				Err(ReadNpyError::WrongDescriptor(_)) => Ok(None),
+				Err(err) => Err(err.into()),
+			}
+		})
+		.wrap_err_with(|| format!("Cannot stack {:?}", name))?;
+	let Some(array) = array else {
+		return Ok(None);
+	};
+	write_npy_f16(zip, &array)
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	Ok(Some(array.shape().to_vec()))
+}
+
+// Half-precision float as `ReadableElement` is implemented for neither `half::f16` nor its bits.
+#[cfg(feature = "half")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct F16(half::f16);
+
+#[cfg(feature = "half")]
+impl ReadableElement for F16 {
+	fn read_to_end_exact_vec<R: Read>(
+		reader: R,
+		type_desc: &py_literal::Value,
+		len: usize,
+	) -> Result<Vec<Self>, ReadDataError> {
+		let bits = match type_desc {
+			py_literal::Value::String(descr) if matches!(descr.as_str(), "<f2" | ">f2") => {
+				// Reads the bits as unsigned integers of the same byte order.
+				let descr = py_literal::Value::String(descr.replace('f', "u"));
+				u16::read_to_end_exact_vec(reader, &descr, len)?
+			}
+			other => return Err(ReadDataError::WrongDescriptor(other.clone())),
+		};
+		Ok(bits
+			.into_iter()
+			.map(|bits| Self(half::f16::from_bits(bits)))
+			.collect())
+	}
+}
+
+#[cfg(feature = "half")]
+impl From<F16> for f32 {
+	fn from(F16(value): F16) -> Self {
+		value.into()
+	}
+}
+
+#[cfg(feature = "half")]
+impl From<F16> for f64 {
+	fn from(F16(value): F16) -> Self {
+		value.into()
+	}
+}
+
+// Writes the array in its memory order as `WritableElement` is an unsafe trait.
+#[cfg(feature = "half")]
+fn write_npy_f16<W: Write>(writer: &mut W, array: &ArrayD<F16>) -> Result<()> {
+	let fortran_order = !array.is_standard_layout();
+	let array = if fortran_order {
+		array.t()
+	} else {
+		array.view()
+	};
+	let mut npy = npy_preamble("<f2", fortran_order, array.shape())?;
+	npy.extend(array.iter().flat_map(|F16(value)| value.to_le_bytes()));
+	writer.write_all(&npy)?;
+	Ok(())
+}
+
+// Reads the arrays of the files one at a time. Stacking along the first axis fills an array
+// preallocated by the shapes of the headers, dropping each array once copied, so peak memory is
+// one input array plus the stacked one. Otherwise, the arrays are concatenated at once.
+#[allow(clippy::too_many_arguments)]
+fn read_stack_npy<A, F>(
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+	reshape: StackShape,
+	order: NpyOrder,
+	mut read: F,
+) -> Result<Option<ArrayD<A>>>
+where
+	A: Clone,
+	F: FnMut(&mut dyn Read, &Path) -> Result<Option<ArrayD<A>>>,
+{
+	let gzipped = Format::new(name) == Some(Format::NpyGz);
+	let flatten = reshape == StackShape::Flatten;
+	let axis = if flatten { 0 } else { axis };
+	let mut shapes = Vec::new();
+	if axis == 0 && reshape != StackShape::Broadcast {
+		for (input, index) in files.iter().copied() {
+			let file = zips.get(input)?.by_index(index)?;
+			let npy = if gzipped {
+				read_npy_header(GzDecoder::new(file))
+			} else {
+				read_npy_header(file)
+			}?;
+			match npy.as_deref().and_then(npy_shape) {
+				Some(shape) if flatten => shapes.push(vec![shape.iter().product()]),
+				Some(shape) if !shape.is_empty() => shapes.push(shape),
+				_ => break,
+			}
+		}
+	}
+	let stackable =
+		shapes.len() == files.len() && shapes.windows(2).all(|pair| pair[0][1..] == pair[1][1..]);
+	let mut shape = shapes.first().cloned().unwrap_or_default();
+	if let Some(length) = shape.first_mut() {
+		*length = shapes.iter().map(|shape| shape[0]).sum();
+	}
+	let size = shape
+		.iter()
+		.try_fold(1usize, |size, &length| size.checked_mul(length));
+	if let Some(size) = size.filter(|_size| stackable) {
+		let mut data = Vec::new();
+		data.try_reserve_exact(size)
+			.wrap_err_with(|| format!("Cannot allocate stacked shape {:?}", shape))?;
+		for ((input, index), expected) in files.iter().copied().zip(&shapes) {
+			let from = zips.paths[input].clone();
+			let file = zips.get(input)?.by_index(index)?;
+			let array = if gzipped {
+				read(&mut GzDecoder::new(file), &from)
+			} else {
+				let mut file = file;
+				read(&mut file, &from)
+			}?;
+			let Some(array) = array else {
+				return Ok(None);
+			};
+			let shape = if flatten {
+				vec![array.len()]
+			} else {
+				array.shape().to_vec()
+			};
+			if shape != *expected {
+				return Err(eyre!(
+					"Mismatching shape {:?} of {:?} and its header",
+					array.shape(),
+					from
+				));
+			}
+			data.extend(array.iter().cloned());
+		}
+		let array = ArrayD::from_shape_vec(shape, data)?;
+		return Ok(Some(order_npy(array, order)));
+	}
+	let mut arrays = Vec::new();
+	let mut from = Vec::new();
+	for (input, index) in files.iter().copied() {
+		from.push(zips.paths[input].clone());
+		let file = zips.get(input)?.by_index(index)?;
+		let array = if gzipped {
+			read(&mut GzDecoder::new(file), &from[from.len() - 1])
+		} else {
+			let mut file = file;
+			read(&mut file, &from[from.len() - 1])
+		}?;
+		let Some(array) = array else {
+			return Ok(None);
+		};
+		arrays.push(if flatten { flatten_npy(array) } else { array });
+	}
+	concatenate_npy(arrays, &from, axis, reshape, order).map(Some)
+}
+
+// Represents integers beyond 2^53 as strings as JSON parsers commonly read numbers as doubles.
+fn json_u64(value: u64) -> Value {
+	if value < 1 << 53 {
+		value.into()
+	} else {
+		value.to_string().into()
+	}
+}
+
+// Reads the header of the NPY file without reading its data.
+fn read_npy_header<R: Read>(mut reader: R) -> io::Result<Option<Vec<u8>>> {
+	let mut npy = vec![0; 12];
+	reader.read_exact(&mut npy)?;
+	let Some(header) = npy_header_range(&npy).filter(|header| header.end >= npy.len()) else {
+		return Ok(None);
+	};
+	npy.resize(header.end, 0);
+	reader.read_exact(&mut npy[12..])?;
+	Ok(Some(npy))
+}
+
+// Selects the data-type of --stack-cast all arrays are cast to unless they agree, along with the
+// data-types cast from.
+fn cast_dtype(
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	casts: &[(StackDtype, StackDtype)],
+) -> Result<Option<(StackDtype, Vec<StackDtype>)>> {
+	let gzipped = Format::new(name) == Some(Format::NpyGz);
+	let mut dtypes = Vec::new();
+	for (input, index) in files.iter().copied() {
+		let file = zips.get(input)?.by_index(index)?;
+		let npy = if gzipped {
+			read_npy_header(GzDecoder::new(file))
+		} else {
+			read_npy_header(file)
+		}?;
+		let Some(dtype) = npy.as_deref().and_then(npy_dtype) else {
+			return Ok(None);
+		};
+		dtypes.push((dtype, input));
+	}
+	if dtypes.windows(2).all(|pair| pair[0].0 == pair[1].0) {
+		return Ok(None);
+	}
+	let castable = |from, to| from == to || casts.contains(&(from, to));
+	let (target, target_input) = dtypes
+		.iter()
+		.copied()
+		.max_by_key(|&(to, _input)| {
+			dtypes
+				.iter()
+				.filter(|&&(from, _input)| castable(from, to))
+				.count()
+		})
+		.unwrap();
+	if let Some(&(from, input)) = dtypes
+		.iter()
+		.find(|&&(from, _input)| !castable(from, target))
+	{
+		return Err(eyre!(
+			"Cannot cast {} of {:?} to {} of {:?} as not listed by --stack-cast",
+			from.name(),
+			zips.paths[input],
+			target.name(),
+			zips.paths[target_input]
+		));
+	}
+	let sources = dtypes.into_iter().map(|(from, _input)| from).collect();
+	Ok(Some((target, sources)))
+}
+
+// Writes a temporary ZIP archive of a zero-filled copy of the NPY file.
+fn zero_npy(zips: &mut Inputs, (input, index): (usize, usize), name: &Path) -> Result<TempPath> {
+	let gzipped = Format::new(name) == Some(Format::NpyGz);
+	let mut npy = Vec::new();
+	let file = zips.get(input)?.by_index(index)?;
+	if gzipped {
+		GzDecoder::new(file).read_to_end(&mut npy)
+	} else {
+		let mut file = file;
+		file.read_to_end(&mut npy)
+	}
+	.wrap_err_with(|| format!("Cannot read {:?}", name))?;
+	let header = npy_header(&npy).ok_or_else(|| eyre!("Invalid NPY file {:?}", name))?;
+	npy[header.end..].fill(0);
+	if gzipped {
+		let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+		encoder
+			.write_all(&npy)
+			.wrap_err_with(|| format!("Cannot compress {:?}", name))?;
+		npy = encoder
+			.finish()
+			.wrap_err_with(|| format!("Cannot compress {:?}", name))?;
+	}
+	let temporary = tempfile::Builder::new()
+		.suffix(".zip")
+		.tempfile()
+		.wrap_err("Cannot create temporary file")?;
+	let mut zip = ZipWriter::new(temporary.as_file());
+	let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+	zip.start_file(name.to_str().unwrap(), options)
+		.and_then(|()| zip.write_all(&npy).map_err(From::from))
+		.and_then(|()| zip.finish().map(drop))
+		.wrap_err("Cannot write temporary file")?;
+	drop(zip);
+	Ok(temporary.into_temp_path())
+}
+
+#[derive(Clone)]
+struct RawSpec {
+	descr: String,
+	shape: Vec<usize>,
+	fortran_order: bool,
+	length: usize,
+}
+
+fn read_raw_spec(path: &Path) -> Result<RawSpec> {
+	let spec = serde_json::from_slice::<Value>(&fs::read(path)?)?;
+	let shape = spec
+		.get("shape")
+		.and_then(Value::as_array)
+		.and_then(|shape| {
+			shape
+				.iter()
+				.map(|length| {
+					length
+						.as_u64()
+						.and_then(|length| usize::try_from(length).ok())
+				})
+				.collect::<Option<Vec<_>>>()
+		})
+		.ok_or_else(|| eyre!("Missing or invalid shape"))?;
+	let dtype = spec
+		.get("dtype")
+		.and_then(Value::as_str)
+		.ok_or_else(|| eyre!("Missing or invalid dtype"))?;
+	let (descr, dtype) = match StackDtype::from_str(dtype, true) {
+		Ok(name) => (name.descr().to_string(), name),
+		Err(_) => StackDtype::from_descr(dtype)
+			.map(|descr| (dtype.to_string(), descr))
+			.ok_or_else(|| eyre!("Unsupported dtype {:?}", dtype))?,
+	};
+	let fortran_order = match spec.get("fortran_order") {
+		Some(order) => order
+			.as_bool()
+			.ok_or_else(|| eyre!("Invalid fortran_order"))?,
+		None => false,
+	};
+	let (_kind, bits) = dtype.kind();
+	let length = shape
+		.iter()
+		.try_fold(bits.div_ceil(8) as usize, |length, &axis| {
+			length.checked_mul(axis)
+		})
+		.ok_or_else(|| eyre!("Invalid shape {:?} exceeding memory", shape))?;
+	Ok(RawSpec {
+		descr,
+		shape,
+		fortran_order,
+		length,
+	})
+}
+
+// Writes a temporary ZIP archive of the raw file with the NPY header of --raw-spec.
+fn raw_npy(
+	zips: &mut Inputs,
+	(input, index): (usize, usize),
+	name: &Path,
+	spec: &RawSpec,
+) -> Result<TempPath> {
+	let mut file = zips.get(input)?.by_index(index)?;
+	if file.size() != spec.length as u64 {
+		return Err(eyre!(
+			"Mismatching size of {} bytes instead of {} bytes as described",
+			file.size(),
+			spec.length
+		));
+	}
+	let preamble = npy_preamble(&spec.descr, spec.fortran_order, &spec.shape)?;
+	let temporary = tempfile::Builder::new()
+		.suffix(".zip")
+		.tempfile()
+		.wrap_err("Cannot create temporary file")?;
+	let mut zip = ZipWriter::new(temporary.as_file());
+	let options = FileOptions::default()
+		.compression_method(CompressionMethod::Stored)
+		.last_modified_time(file.last_modified())
+		.large_file(spec.length as u64 > u64::from(u32::MAX));
+	let options = file
+		.unix_mode()
+		.map_or(options, |mode| options.unix_permissions(mode));
+	zip.start_file(name.to_str().unwrap(), options)
+		.wrap_err("Cannot write temporary file")?;
+	zip.write_all(&preamble)
+		.wrap_err("Cannot write temporary file")?;
+	copy(&mut file, &mut zip).wrap_err("Cannot copy raw file")?;
+	zip.finish().wrap_err("Cannot write temporary file")?;
+	drop(zip);
+	Ok(temporary.into_temp_path())
+}
+
+// Magic string, version 1.0, and header of an NPY file.
+fn npy_preamble(descr: &str, fortran_order: bool, shape: &[usize]) -> Result<Vec<u8>> {
+	let shape = match shape {
+		[length] => format!("({},)", length),
+		shape => {
+			let shape = shape.iter().map(ToString::to_string).collect::<Vec<_>>();
+			format!("({})", shape.join(", "))
+		}
+	};
+	let order = if fortran_order { "True" } else { "False" };
+	let mut header = format!(
+		"{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}",
+		descr, order, shape
+	);
+	// Pads the header with spaces and a newline to align the data to 64 bytes.
+	let padding = 63 - (10 + header.len()) % 64;
+	header.extend(std::iter::repeat_n(' ', padding));
+	header.push('\n');
+	let length = u16::try_from(header.len()).wrap_err("Invalid shape exceeding NPY header")?;
+	let mut preamble = b"\x93NUMPY\x01\x00".to_vec();
+	preamble.extend(length.to_le_bytes());
+	preamble.extend(header.as_bytes());
+	Ok(preamble)
+}
+
+fn npy_header(npy: &[u8]) -> Option<Range<usize>> {
+	npy_header_range(npy).filter(|header| header.end <= npy.len())
+}
+
+fn npy_header_range(npy: &[u8]) -> Option<Range<usize>> {
+	match npy {
+		[0x93, b'N', b'U', b'M', b'P', b'Y', 1, _, length_0, length_1, ..] => {
+			let length = usize::from(u16::from_le_bytes([*length_0, *length_1]));
+			Some(10..10 + length)
+		}
+		[0x93, b'N', b'U', b'M', b'P', b'Y', 2 | 3, _, length_0, length_1, length_2, length_3, ..] =>
+		{
+			let length = u32::from_le_bytes([*length_0, *length_1, *length_2, *length_3]);
+			Some(12..12usize.checked_add(usize::try_from(length).ok()?)?)
+		}
+		_ => None,
+	}
+}
+
+fn npy_shape(npy: &[u8]) -> Option<Vec<usize>> {
+	let header = std::str::from_utf8(&npy[npy_header(npy)?]).ok()?;
+	let (_dict, shape) = header.split_once("'shape':")?;
+	let shape = shape.trim_start().strip_prefix('(')?;
+	let (shape, _dict) = shape.split_once(')')?;
+	shape
+		.split(',')
+		.map(str::trim)
+		.filter(|length| !length.is_empty())
+		.map(|length| length.parse().ok())
+		.collect()
+}
+
+fn npy_dtype(npy: &[u8]) -> Option<StackDtype> {
+	let header = std::str::from_utf8(&npy[npy_header(npy)?]).ok()?;
+	let (_dict, descr) = header.split_once("'descr':")?;
+	let descr = descr.trim_start().strip_prefix('\'')?;
+	let (descr, _dict) = descr.split_once('\'')?;
+	StackDtype::from_descr(descr)
+}
+
+fn concatenate_npy<A: Clone>(
+	arrays: Vec<ArrayD<A>>,
+	from: &[PathBuf],
+	axis: usize,
+	reshape: StackShape,
+	order: NpyOrder,
+) -> Result<ArrayD<A>> {
+	let shapes = arrays
+		.iter()
+		.zip(from)
+		.map(|(array, from)| format!("{:?} of {:?}", array.shape(), from))
+		.collect::<Vec<_>>();
+	let arrays = if reshape == StackShape::Broadcast {
+		broadcast_npy(arrays, axis)?
+	} else {
+		arrays
+	};
+	if let Some(array) = arrays.iter().find(|array| axis >= array.ndim()) {
+		return Err(eyre!(
+			"Invalid stack axis {} for {} dimensions",
+			axis,
+			array.ndim()
+		));
+	}
+	let arrays = arrays.iter().map(ArrayD::view).collect::<Vec<_>>();
+	let array = ndarray::concatenate(Axis(axis), &arrays).wrap_err_with(|| {
+		format!(
+			"Cannot stack shapes {} along axis {}",
+			shapes.join(", "),
+			axis
+		)
+	})?;
+	Ok(order_npy(array, order))
+}
+
+// Ravels the array in C order to one dimension.
+fn flatten_npy<A: Clone>(array: ArrayD<A>) -> ArrayD<A> {
+	let length = array.len();
+	if array.is_standard_layout() {
+		array.into_shape(IxDyn(&[length])).unwrap()
+	} else {
+		ArrayD::from_shape_vec(IxDyn(&[length]), array.iter().cloned().collect()).unwrap()
+	}
+}
+
+// Appending may leave the array in either or no memory order depending on the inputs.
+fn order_npy<A: Clone>(array: ArrayD<A>, order: NpyOrder) -> ArrayD<A> {
+	match order {
+		NpyOrder::C if array.is_standard_layout() => array,
+		NpyOrder::C => array.as_standard_layout().into_owned(),
+		NpyOrder::F if array.t().is_standard_layout() => array,
+		NpyOrder::F => array
+			.reversed_axes()
+			.as_standard_layout()
+			.into_owned()
+			.reversed_axes(),
+	}
+}
+
+fn broadcast_npy<A: Clone>(arrays: Vec<ArrayD<A>>, axis: usize) -> Result<Vec<ArrayD<A>>> {
+	let ndim = arrays.iter().map(ArrayD::ndim).max().unwrap_or(0);
+	if axis >= ndim {
+		return Err(eyre!("Invalid stack axis {} for {} dimensions", axis, ndim));
+	}
+	let mut common: Option<(&[usize], Vec<usize>)> = None;
+	for array in arrays.iter().filter(|array| array.ndim() == ndim) {
+		let mut shape = array.shape().to_vec();
+		shape[axis] = 1;
+		match &common {
+			Some((first, common)) if *common != shape => {
+				return Err(eyre!(
+					"Ambiguous broadcast of shapes {:?} and {:?}",
+					first,
+					array.shape()
+				));
+			}
+			Some(_) => {}
+			None => common = Some((array.shape(), shape)),
+		}
+	}
+	let (_first, shape) = common.unwrap();
+	arrays
+		.iter()
+		.map(|array| {
+			if array.ndim() == ndim {
+				Ok(array.clone())
+			} else {
+				array
+					.broadcast(shape.as_slice())
+					.map(|array| array.to_owned())
+					.ok_or_else(|| {
+						eyre!("Cannot broadcast shape {:?} to {:?}", array.shape(), shape)
+					})
+			}
+		})
+		.collect()
+}
+
+fn stack_csv<W: Write>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+	header: bool,
+) -> Result<()> {
+	let name = || format!("Cannot stack {:?}", name);
+	if axis != 0 {
+		return Err(eyre!("Invalid stack axis {} for rows", axis)).wrap_err_with(name);
+	}
+	let mut writer = csv::Writer::from_writer(zip);
+	let mut first = None;
+	for (input, index) in files.iter().copied() {
+		let from = zips.paths[input].clone();
+		let file = zips.get(input)?.by_index(index)?;
+		let mut reader = csv::ReaderBuilder::new()
+			.has_headers(header)
+			.from_reader(file);
+		if header {
+			let headers = reader.byte_headers().wrap_err_with(name)?.clone();
+			match &first {
+				Some((first, first_from)) if *first != headers => {
+					return Err(eyre!(
+						"Mismatching headers of {:?} and {:?}",
+						first_from,
+						from
+					))
+					.wrap_err_with(name);
+				}
+				Some(_) => {}
+				None => {
+					writer.write_byte_record(&headers).wrap_err_with(|| {
+						format!("Cannot write file to output ZIP archive {:?}", path)
+					})?;
+					first = Some((headers, from));
+				}
+			}
+		}
+		let mut record = csv::ByteRecord::new();
+		while reader.read_byte_record(&mut record).wrap_err_with(name)? {
+			writer
+				.write_byte_record(&record)
+				.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+		}
+	}
+	writer
+		.flush()
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))
+}
+
+fn stack_json<W: Write>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+	merge: JsonMerge,
+) -> Result<()> {
+	let name = || format!("Cannot stack {:?}", name);
+	if axis != 0 {
+		return Err(eyre!("Invalid stack axis {} for top levels", axis)).wrap_err_with(name);
+	}
+	let mut array = Vec::new();
+	let mut object = serde_json::Map::new();
+	for (input, index) in files.iter().copied() {
+		let from = zips.paths[input].clone();
+		let file = zips.get(input)?.by_index(index)?;
+		let value = serde_json::from_reader(BufReader::new(file))
+			.wrap_err_with(|| format!("Invalid JSON file of {:?}", from))
+			.wrap_err_with(name)?;
+		match (merge, value) {
+			(JsonMerge::Array, Value::Array(values)) => array.extend(values),
+			(JsonMerge::Object, Value::Object(map)) => object.extend(map),
+			(JsonMerge::Array, _value) => {
+				return Err(eyre!("Top level of {:?} is not an array", from)).wrap_err_with(name);
+			}
+			(JsonMerge::Object, _value) => {
+				return Err(eyre!("Top level of {:?} is not an object", from)).wrap_err_with(name);
+			}
+		}
+	}
+	let value = match merge {
+		JsonMerge::Array => Value::Array(array),
+		JsonMerge::Object => Value::Object(object),
+	};
+	serde_json::to_writer(zip, &value)
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))
+}
+
+#[cfg(feature = "parquet")]
+fn try_stack_parquet<W: Write>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	axis: usize,
+) -> Result<()> {
+	use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter};
+
+	let name = || format!("Cannot stack {:?}", name);
+	if axis != 0 {
+		return Err(eyre!("Invalid stack axis {} for row groups", axis)).wrap_err_with(name);
+	}
+	// Row groups are read from seekable temporary files and written into memory as the writer
+	// requires a sendable sink.
+	let mut builders = files.iter().map(|&(input, index)| -> Result<_> {
+		let from = zips.paths[input].clone();
+		let mut file = zips.get(input)?.by_index(index)?;
+		let mut temporary = tempfile::tempfile()?;
+		copy(&mut file, &mut temporary).wrap_err_with(name)?;
+		let builder = ParquetRecordBatchReaderBuilder::try_new(temporary).wrap_err_with(name)?;
+		Ok((builder, from))
+	});
+	let (builder, first_from) = builders.next().unwrap()?;
+	let schema = builder.schema().clone();
+	let mut writer = ArrowWriter::try_new(Vec::new(), schema.clone(), None).wrap_err_with(name)?;
+	for built in std::iter::once(Ok((builder, first_from.clone()))).chain(builders) {
+		let (builder, from) = built?;
+		if builder.schema().fields() != schema.fields() {
+			return Err(eyre!(
+				"Mismatching schemas of {:?} and {:?}",
+				first_from,
+				from
+			))
+			.wrap_err_with(name);
+		}
+		for batch in builder.build().wrap_err_with(name)? {
+			let batch = batch.and_then(|batch| batch.with_schema(schema.clone()));
+			writer
+				.write(&batch.wrap_err_with(name)?)
+				.wrap_err_with(name)?;
+		}
+	}
+	let buffer = writer.into_inner().wrap_err_with(name)?;
+	zip.write_all(&buffer)
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))
+}
+
+fn select_file(
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	collision: CollisionPolicy,
+) -> Result<(usize, usize)> {
+	let mut selected = files.last().copied().unwrap();
+	match collision {
+		CollisionPolicy::Last => {}
+		CollisionPolicy::First => selected = files[0],
+		CollisionPolicy::Newest => {
+			let mut newest = None;
+			for (input, index) in files.iter().copied() {
+				let time = time_key(zips.get(input)?.by_index(index)?.last_modified());
+				if newest.is_none_or(|newest| time >= newest) {
+					(newest, selected) = (Some(time), (input, index));
+				}
+			}
+		}
+		CollisionPolicy::Largest => {
+			let mut largest = None;
+			for (input, index) in files.iter().copied() {
+				let size = zips.get(input)?.by_index(index)?.size();
+				if largest.is_none_or(|largest| size >= largest) {
+					(largest, selected) = (Some(size), (input, index));
+				}
+			}
+		}
+	}
+	Ok(selected)
+}
+
+// Sorts by the given paths of input ZIP archives, which differ from the temporary ones of
+// --raw-spec.
+fn sort_files(
+	zips: &mut Inputs,
+	paths: &[PathBuf],
+	files: &[(usize, usize)],
+	order: StackOrder,
+) -> Result<Vec<(usize, usize)>> {
+	let mut files = files.to_vec();
+	match order {
+		StackOrder::Name => files.sort_by(|&(a, _), &(b, _)| paths[a].cmp(&paths[b])),
+		StackOrder::Natural => files.sort_by(|&(a, _), &(b, _)| {
+			natural_cmp(&paths[a].to_string_lossy(), &paths[b].to_string_lossy())
+		}),
+		StackOrder::Mtime => {
+			let mut times = Vec::new();
+			for (input, index) in files.iter().copied() {
+				times.push(time_key(zips.get(input)?.by_index(index)?.last_modified()));
+			}
+			let mut files_times = files.into_iter().zip(times).collect::<Vec<_>>();
+			files_times.sort_by_key(|&(_file, time)| time);
+			files = files_times.into_iter().map(|(file, _time)| file).collect();
+		}
+	}
+	Ok(files)
+}
+
+fn sort_files_by_index(
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	member: &Path,
+	indices: &mut HashMap<usize, i128>,
+) -> Result<Vec<(usize, usize)>> {
+	let mut files_indices = Vec::new();
+	for (input, index) in files.iter().copied() {
+		let key = match indices.get(&input) {
+			Some(&key) => key,
+			None => {
+				let key = read_index(zips, input, member).wrap_err_with(|| {
+					format!("Invalid input ZIP archive {:?}", zips.paths[input])
+				})?;
+				indices.insert(input, key);
+				key
+			}
+		};
+		files_indices.push(((input, index), key));
+	}
+	files_indices.sort_by_key(|&(_file, key)| key);
+	Ok(files_indices.into_iter().map(|(file, _key)| file).collect())
+}
+
+fn read_index(zips: &mut Inputs, input: usize, member: &Path) -> Result<i128> {
+	let zip = zips.get(input)?;
+	for index in 0..zip.len() {
+		let mut file = zip.by_index(index)?;
+		if file.name() != member {
+			continue;
+		}
+		let mut npy = Vec::new();
+		file.read_to_end(&mut npy)
+			.wrap_err_with(|| format!("Cannot read {:?}", member))?;
+		let casts: &[CastNpy<i128>] = &[
+			cast_npy::<i64, _>,
+			cast_npy::<u64, _>,
+			cast_npy::<i32, _>,
+			cast_npy::<u32, _>,
+			cast_npy::<i16, _>,
+			cast_npy::<u16, _>,
+			cast_npy::<i8, _>,
+			cast_npy::<u8, _>,
+		];
+		for cast in casts {
+			if let Some(array) = cast(&npy).wrap_err_with(|| format!("Cannot read {:?}", member))? {
+				return match array.as_slice_memory_order() {
+					Some(&[key]) => Ok(key),
+					_ => Err(eyre!(
+						"Cannot sort by non-scalar index {:?} of shape {:?}",
+						member,
+						array.shape()
+					)),
+				};
+			}
+		}
+		return Err(eyre!("Cannot sort by non-integer index {:?}", member));
+	}
+	Err(eyre!("Cannot sort by missing index {:?}", member))
+}
+
+fn time_key(time: DateTime) -> (u16, u8, u8, u8, u8, u8) {
+	(
+		time.year(),
+		time.month(),
+		time.day(),
+		time.hour(),
+		time.minute(),
+		time.second(),
+	)
+}
+
+// Compares runs of digits by their numeric values.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+	let (mut a, mut b) = (a, b);
+	loop {
+		let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+		let (a_digits, b_digits) = (digits(a), digits(b));
+		let ordering = if a_digits > 0 && b_digits > 0 {
+			let (a_number, b_number) = (
+				a[..a_digits].trim_start_matches('0'),
+				b[..b_digits].trim_start_matches('0'),
+			);
+			let ordering = a_number
+				.len()
+				.cmp(&b_number.len())
+				.then_with(|| a_number.cmp(b_number));
+			(a, b) = (&a[a_digits..], &b[b_digits..]);
+			ordering
+		} else {
+			let mut a_chars = a.chars();
+			let mut b_chars = b.chars();
+			let ordering = a_chars.next().cmp(&b_chars.next());
+			if a.is_empty() && b.is_empty() {
+				return Ordering::Equal;
+			}
+			(a, b) = (a_chars.as_str(), b_chars.as_str());
+			ordering
+		};
+		if ordering != Ordering::Equal {
+			return ordering;
+		}
+	}
+}
+
+fn stack_command<W: Write>(
+	path: &Path,
+	zip: &mut W,
+	zips: &mut Inputs,
+	files: &[(usize, usize)],
+	name: &Path,
+	command: &str,
+) -> Result<()> {
+	let suffix = name
+		.extension()
+		.map(|extension| format!(".{}", extension.to_string_lossy()))
+		.unwrap_or_default();
+	let name = || format!("Cannot stack {:?}", name);
+	let mut members = Vec::new();
+	for (input, index) in files.iter().copied() {
+		let mut member = tempfile::Builder::new()
+			.suffix(&suffix)
+			.tempfile()
+			.wrap_err("Cannot create temporary file")?;
+		copy(&mut zips.get(input)?.by_index(index)?, &mut member).wrap_err_with(name)?;
+		members.push(member.into_temp_path());
+	}
+	let mut arguments = command.split_whitespace();
+	let mut child = Command::new(arguments.next().unwrap())
+		.args(arguments)
+		.args(&members)
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.spawn()
+		.wrap_err_with(|| format!("Cannot run stack command {:?}", command))
+		.wrap_err_with(name)?;
+	let mut stdout = child.stdout.take().unwrap();
+	copy(&mut stdout, zip)
+		.wrap_err_with(|| format!("Cannot write file to output ZIP archive {:?}", path))?;
+	let status = child
+		.wait()
+		.wrap_err_with(|| format!("Cannot run stack command {:?}", command))
+		.wrap_err_with(name)?;
+	if status.success() {
+		Ok(())
+	} else {
+		Err(eyre!("Stack command {:?} failed with {}", command, status)).wrap_err_with(name)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+	use std::iter;
+	use tempfile::TempDir;
+
+	// Writes a ZIP archive of stored files and, for names ending with a slash, directories.
+	fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+		let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+		let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+		for (name, data) in files {
+			if name.ends_with('/') {
+				zip.add_directory(*name, options).unwrap();
+			} else {
+				zip.start_file(*name, options).unwrap();
+				zip.write_all(data).unwrap();
+			}
+		}
+		zip.finish().unwrap();
+	}
+
+	// Reads the names and data of the files of a ZIP archive in order.
+	fn read_zip(path: &Path) -> Vec<(String, Vec<u8>)> {
+		let mut zip = ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+		(0..zip.len())
+			.map(|index| {
+				let mut file = zip.by_index(index).unwrap();
+				let mut data = Vec::new();
+				file.read_to_end(&mut data).unwrap();
+				(file.name().to_string(), data)
+			})
+			.collect()
+	}
+
+	// Runs rezip in the given directory with its paths relative to it.
+	fn rezip(dir: &TempDir, args: &[&str]) -> Result<()> {
+		let args = args.iter().map(|arg| match arg.strip_prefix('@') {
+			Some(path) => dir.path().join(path).into_os_string(),
+			None => arg.into(),
+		});
+		Rezip::try_parse_from(iter::once("rezip".into()).chain(args))?
+			.run_with_progress(|_event| {})
+	}
+
+	#[test]
+	fn duplicate_names_in_input() {
+		let dir = tempfile::tempdir().unwrap();
+		write_zip(
+			&dir.path().join("in.zip"),
+			&[("a.txt", b"first"), ("b.txt", b"b"), ("a.txt", b"last")],
+		);
+		let err = rezip(&dir, &["@in.zip", "-o", "@out.zip"]).unwrap_err();
+		assert!(format!("{:?}", err).contains("Duplicate file name \"a.txt\""));
+		assert!(!dir.path().join("out.zip").exists());
+		rezip(
+			&dir,
+			&["@in.zip", "-o", "@out.zip", "--allow-duplicate-inputs"],
+		)
+		.unwrap();
+		let files = read_zip(&dir.path().join("out.zip"));
+		assert_eq!(
+			files,
+			[
+				("a.txt".into(), b"last".to_vec()),
+				("b.txt".into(), b"b".to_vec())
+			]
+		);
+	}
+
+	// Writes an array of the given shape in standard layout as NPY file.
+	fn npy<A: WritableElement>(shape: &[usize], data: Vec<A>) -> Vec<u8> {
+		let mut npy = Vec::new();
+		ArrayD::from_shape_vec(shape, data)
+			.unwrap()
+			.write_npy(&mut npy)
+			.unwrap();
+		npy
+	}
+
+	// Reads an NPY file as array of dynamic dimension.
+	fn read_npy<A: ReadableElement>(npy: &[u8]) -> ArrayD<A> {
+		ArrayD::read_npy(npy).unwrap()
+	}
+
+	#[test]
+	fn stack_npz_members() {
+		let dir = tempfile::tempdir().unwrap();
+		for (path, offset) in [("a.npz", 0), ("b.npz", 100)] {
+			let images = npy(&[1, 2, 2], (offset..offset + 4).collect::<Vec<i64>>());
+			let labels = npy(&[1], vec![offset as f32]);
+			write_zip(
+				&dir.path().join(path),
+				&[("images.npy", &images), ("labels.npy", &labels)],
+			);
+		}
+		rezip(
+			&dir,
+			&[
+				"@a.npz",
+				"@b.npz",
+				"-o",
+				"@out.npz",
+				"--stack",
+				"images.npy=0",
+				"--stack",
+				"labels.npy=0",
+			],
+		)
+		.unwrap();
+		let files = read_zip(&dir.path().join("out.npz"));
+		assert_eq!(files.len(), 2);
+		assert_eq!(files[0].0, "images.npy");
+		let images = read_npy::<i64>(&files[0].1);
+		assert_eq!(images.shape(), [2, 2, 2]);
+		assert_eq!(
+			images.iter().copied().collect::<Vec<_>>(),
+			[0, 1, 2, 3, 100, 101, 102, 103]
+		);
+		assert_eq!(files[1].0, "labels.npy");
+		let labels = read_npy::<f32>(&files[1].1);
+		assert_eq!(labels.shape(), [2]);
+		assert_eq!(labels.iter().copied().collect::<Vec<_>>(), [0.0, 100.0]);
+	}
+
+	#[test]
+	fn nested_empty_directories() {
+		let dir = tempfile::tempdir().unwrap();
+		fs::create_dir_all(dir.path().join("tree/a/b/c/d")).unwrap();
+		fs::create_dir_all(dir.path().join("tree/x/y")).unwrap();
+		fs::write(dir.path().join("tree/x/y/f.txt"), b"f").unwrap();
+		rezip(&dir, &["@tree", "-o", "@dir.zip"]).unwrap();
+		// Files of input directories are named by their paths as given.
+		let prefix = format!("{}/", dir.path().to_str().unwrap());
+		let mut names = read_zip(&dir.path().join("dir.zip"))
+			.into_iter()
+			.filter_map(|(name, _data)| {
+				let name = name.strip_prefix(&prefix)?;
+				(!name.is_empty()).then(|| name.to_string())
+			})
+			.collect::<Vec<_>>();
+		names.sort();
+		assert_eq!(
+			names,
+			[
+				"tree/",
+				"tree/a/",
+				"tree/a/b/",
+				"tree/a/b/c/",
+				"tree/a/b/c/d/",
+				"tree/x/",
+				"tree/x/y/",
+				"tree/x/y/f.txt"
+			]
+		);
+		// Parents of the empty directory and of the file are implicit.
+		write_zip(
+			&dir.path().join("in.zip"),
+			&[("a/b/c/d/", b""), ("x/y/f.txt", b"f")],
+		);
+		rezip(&dir, &["@in.zip", "-o", "@zip.zip"]).unwrap();
+		let mut names = read_zip(&dir.path().join("zip.zip"))
+			.into_iter()
+			.map(|(name, _data)| name)
+			.collect::<Vec<_>>();
+		names.sort();
+		assert_eq!(
+			names,
+			[
+				"a/",
+				"a/b/",
+				"a/b/c/",
+				"a/b/c/d/",
+				"x/",
+				"x/y/",
+				"x/y/f.txt"
+			]
+		);
+	}
+
+	#[test]
+	fn stack_fortran_order() {
+		use ndarray::{IxDyn, ShapeBuilder};
+		let dir = tempfile::tempdir().unwrap();
+		let mut fortran = Vec::new();
+		ArrayD::from_shape_vec(IxDyn(&[2, 2]).f(), vec![0i64, 2, 1, 3])
+			.unwrap()
+			.write_npy(&mut fortran)
+			.unwrap();
+		assert!(String::from_utf8_lossy(&fortran).contains("'fortran_order': True"));
+		write_zip(&dir.path().join("f.npz"), &[("x.npy", &fortran)]);
+		let c = npy(&[2, 2], vec![4i64, 5, 6, 7]);
+		write_zip(&dir.path().join("c.npz"), &[("x.npy", &c)]);
+		for (order, fortran_order) in [("c", "False"), ("f", "True")] {
+			let output = format!("@out-{}.npz", order);
+			rezip(
+				&dir,
+				&["@f.npz", "@c.npz", "-o", &output, "--npy-order", order],
+			)
+			.unwrap();
+			let files = read_zip(&dir.path().join(&output[1..]));
+			let header = format!("'fortran_order': {}", fortran_order);
+			assert!(String::from_utf8_lossy(&files[0].1).contains(&header));
+			let x = read_npy::<i64>(&files[0].1);
+			assert_eq!(x.shape(), [4, 2]);
+			assert_eq!(
+				x.iter().copied().collect::<Vec<_>>(),
+				(0..8).collect::<Vec<_>>()
+			);
+		}
+	}
+
+	#[test]
+	fn dedup_overlapping_directories() {
+		let dir = tempfile::tempdir().unwrap();
+		write_zip(
+			&dir.path().join("a.zip"),
+			&[("lib/", b""), ("lib/x/", b""), ("lib/x/a.txt", b"a")],
+		);
+		write_zip(
+			&dir.path().join("b.zip"),
+			&[("Lib/", b""), ("lib\\x\\", b""), ("lib/x/b.txt", b"b")],
+		);
+		// Directory inputs name directories without trailing slash as in lib.
+		fs::create_dir_all(dir.path().join("lib/X")).unwrap();
+		fs::write(dir.path().join("lib/X/c.txt"), b"c").unwrap();
+		let base = dir.path().to_str().unwrap();
+		let args = ["@a.zip", "@b.zip", "@lib", "--base", base, "-o"];
+		let names = |path: &str| {
+			read_zip(&dir.path().join(path))
+				.into_iter()
+				.map(|(name, _data)| name)
+				.collect::<Vec<_>>()
+		};
+		rezip(&dir, &[&args[..], &["@all.zip"]].concat()).unwrap();
+		assert_eq!(
+			names("all.zip"),
+			[
+				"lib/",
+				"lib/x/",
+				"lib/x/a.txt",
+				"Lib/",
+				"lib\\x\\",
+				"lib/x/b.txt",
+				"lib/X/",
+				"lib/X/c.txt"
+			]
+		);
+		rezip(&dir, &[&args[..], &["@dedup.zip", "--dedup-dirs"]].concat()).unwrap();
+		assert_eq!(
+			names("dedup.zip"),
+			[
+				"lib/",
+				"lib/x/",
+				"lib/x/a.txt",
+				"lib/x/b.txt",
+				"lib/X/c.txt"
+			]
+		);
+	}
+
+	// Finds the general purpose bit flags of local and central file headers.
+	fn flag_offsets(zip: &[u8]) -> Vec<usize> {
+		zip.windows(4)
+			.enumerate()
+			.filter_map(|(start, signature)| match signature {
+				b"PK\x03\x04" => Some(start + 6),
+				b"PK\x01\x02" => Some(start + 8),
+				_ => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn utf8_names_round_trip() {
+		let dir = tempfile::tempdir().unwrap();
+		let name = "größe/ß.txt";
+		write_zip(&dir.path().join("in.zip"), &[(name, b"data")]);
+		// Clears the language encoding flag as written by legacy archivers.
+		let mut zip = fs::read(dir.path().join("in.zip")).unwrap();
+		for offset in flag_offsets(&zip) {
+			zip[offset + 1] &= !(1 << 3);
+		}
+		fs::write(dir.path().join("in.zip"), &zip).unwrap();
+		assert_ne!(read_zip(&dir.path().join("in.zip"))[0].0, name);
+		rezip(&dir, &["@in.zip", "-o", "@out.zip", "--utf8-names"]).unwrap();
+		let files = read_zip(&dir.path().join("out.zip"));
+		let names = files.iter().map(|(name, _data)| name).collect::<Vec<_>>();
+		assert_eq!(names, ["größe/", name]);
+		assert_eq!(files[1].1, b"data");
+		let zip = fs::read(dir.path().join("out.zip")).unwrap();
+		assert_eq!(flag_offsets(&zip).len(), 4);
+		for offset in flag_offsets(&zip) {
+			let flags = u16::from_le_bytes([zip[offset], zip[offset + 1]]);
+			assert_ne!(flags & 1 << 11, 0);
+		}
+	}
+
+	#[test]
+	fn strip_components() {
+		assert_eq!(strip_name("a/b/c.txt", 2), Some("c.txt"));
+		assert_eq!(strip_name("a/b/", 2), None);
+		assert_eq!(strip_name("a/c.txt", 2), None);
+		let dir = tempfile::tempdir().unwrap();
+		write_zip(
+			&dir.path().join("in.zip"),
+			&[
+				("top.txt", b"top"),
+				("a/", b""),
+				("a/one.txt", b"one"),
+				("a/b/", b""),
+				("a/b/two.txt", b"two"),
+				("a/b/c/three.txt", b"three"),
+			],
+		);
+		rezip(
+			&dir,
+			&["@in.zip", "-o", "@out.zip", "--strip-components", "2"],
+		)
+		.unwrap();
+		let files = read_zip(&dir.path().join("out.zip"));
+		assert_eq!(
+			files,
+			[
+				("two.txt".into(), b"two".to_vec()),
+				("c/".into(), Vec::new()),
+				("c/three.txt".into(), b"three".to_vec())
+			]
+		);
+	}
+
+	// Writes a TAR archive of regular files with raw names bypassing the path checks of `tar`.
+	fn tar(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+		let mut builder = tar::Builder::new(Vec::new());
+		for (name, data) in files {
+			let mut header = tar::Header::new_gnu();
+			header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+			header.set_size(data.len() as u64);
+			header.set_mode(0o644);
+			header.set_cksum();
+			builder.append(&header, *data).unwrap();
+		}
+		Cursor::new(builder.into_inner().unwrap())
+	}
+
+	#[test]
+	fn tar_rejects_traversal() {
+		for name in ["/abs.txt", "../up.txt", "a/../../up.txt"] {
+			let err = TarArchive::new(tar(&[("ok.txt", b"ok"), (name, b"up")]))
+				.err()
+				.unwrap_or_else(|| panic!("Accepted {:?}", name));
+			assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		}
+		let zip = TarArchive::new(tar(&[("./a/ok.txt", b"ok")])).unwrap();
+		assert_eq!(zip.files[0].name, "a/ok.txt");
+	}
+
+	#[test]
+	fn glob_qualifiers() {
+		let options = GlobOptions {
+			options: MatchOptions::new(),
+			basename: false,
+		};
+		let glob = Glob::new("e/b:c.txt").unwrap();
+		assert!(glob.from.is_none());
+		assert!(options.matches(&glob, Path::new("e/b:c.txt"), None));
+		let glob = Glob::new("C:/data/*.npy").unwrap();
+		assert!(glob.from.is_none());
+		let glob = Glob::new("vendor.ZIP:*.so").unwrap();
+		assert!(options.matches(&glob, Path::new("a.so"), Some(Path::new("vendor.ZIP"))));
+		assert!(!options.matches(&glob, Path::new("a.so"), Some(Path::new("other.zip"))));
+		assert!(!options.matches(&glob, Path::new("a.so"), None));
+		let glob = Glob::new("a.zip[:]b.txt").unwrap();
+		assert!(glob.from.is_none());
+		assert!(options.matches(&glob, Path::new("a.zip:b.txt"), None));
+	}
+
+	#[test]
+	fn rename_splits_at_first_unescaped_equals() {
+		assert_eq!(
+			split_rename(r"a\.txt=k=v.txt"),
+			Some((r"a\.txt".into(), "k=v.txt"))
+		);
+		assert_eq!(split_rename(r"k\=v=kv"), Some(("k=v".into(), "kv")));
+		assert_eq!(split_rename(r"a\\=b"), Some((r"a\\".into(), "b")));
+		assert_eq!(split_rename("a"), None);
+	}
+
+	#[test]
+	fn no_zip64_alias() {
+		let dir = tempfile::tempdir().unwrap();
+		write_zip(&dir.path().join("in.zip"), &[("a.txt", b"a")]);
+		rezip(&dir, &["@in.zip", "-o", "@alias.zip", "--no-zip64"]).unwrap();
+		rezip(&dir, &["@in.zip", "-o", "@never.zip", "--zip64", "never"]).unwrap();
+		let alias = fs::read(dir.path().join("alias.zip")).unwrap();
+		let never = fs::read(dir.path().join("never.zip")).unwrap();
+		assert_eq!(alias, never);
+		let args = [
+			"@in.zip",
+			"-o",
+			"@out.zip",
+			"--no-zip64",
+			"--zip64",
+			"always",
+		];
+		assert!(rezip(&dir, &args).is_err());
+	}
+
+	#[test]
+	fn aligned_compressed_trailer() {
+		let dir = tempfile::tempdir().unwrap();
+		let data = b"aligned ".repeat(100);
+		write_zip(
+			&dir.path().join("in.zip"),
+			&[("a.bin", &data), ("b.txt", b"stored")],
+		);
+		let args = [
+			"@in.zip",
+			"-o",
+			"@out.zip",
+			"-r",
+			"*.bin=deflated",
+			"--align",
+			"64",
+		];
+		rezip(
+			&dir,
+			&[&args[..], &["--aligned-compressed", "zstd:5"]].concat(),
+		)
+		.unwrap();
+		let mut zip = ZipArchive::new(fs::File::open(dir.path().join("out.zip")).unwrap()).unwrap();
+		let file = zip.by_index(0).unwrap();
+		assert_eq!(file.compression(), CompressionMethod::Stored);
+		assert_eq!(file.data_start() % 64, 0);
+		let files = read_zip(&dir.path().join("out.zip"));
+		assert_eq!(files[1], ("b.txt".into(), b"stored".to_vec()));
+		let (stream, trailer) = files[0].1.split_at(files[0].1.len() - 16);
+		assert_eq!(&trailer[8..], b"zstdRZIP");
+		assert_eq!(trailer[..8], (data.len() as u64).to_le_bytes());
+		assert_eq!(zstd::decode_all(stream).unwrap(), data);
+		for (option, value) in [
+			("--aligned-compressed", "zstd:22"),
+			("--recompress", "zstd:22"),
+		] {
+			assert!(rezip(&dir, &[&args[..], &[option, value, "-f"]].concat()).is_err());
+		}
+	}
+
+	#[cfg(feature = "half")]
+	#[test]
+	fn stack_half_precision() {
+		let f16 = |values: &[f32]| {
+			let values = values.iter().map(|&value| F16(half::f16::from_f32(value)));
+			let mut npy = Vec::new();
+			write_npy_f16(
+				&mut npy,
+				&ArrayD::from_shape_vec(vec![values.len()], values.collect()).unwrap(),
+			)
+			.unwrap();
+			npy
+		};
+		let dir = tempfile::tempdir().unwrap();
+		write_zip(&dir.path().join("a.npz"), &[("x.npy", &f16(&[1.0, -2.0]))]);
+		write_zip(&dir.path().join("b.npz"), &[("x.npy", &f16(&[65504.0]))]);
+		write_zip(
+			&dir.path().join("c.npz"),
+			&[("x.npy", &npy(&[1], vec![0.5f32]))],
+		);
+		rezip(&dir, &["@a.npz", "@b.npz", "-o", "@half.npz"]).unwrap();
+		let x = read_npy::<F16>(&read_zip(&dir.path().join("half.npz"))[0].1);
+		assert_eq!(x.mapv(f32::from).into_raw_vec(), [1.0, -2.0, 65504.0]);
+		let args = ["@a.npz", "@c.npz", "-o"];
+		assert!(rezip(&dir, &[&args[..], &["@none.npz"]].concat()).is_err());
+		for (output, option, value) in [
+			("@dtype.npz", "--stack-dtype", "f32"),
+			("@cast.npz", "--stack-cast", "f16->f32"),
+		] {
+			rezip(&dir, &[&args[..], &[output, option, value]].concat()).unwrap();
+			let x = read_npy::<f32>(&read_zip(&dir.path().join(&output[1..]))[0].1);
+			assert_eq!(x.into_raw_vec(), [1.0, -2.0, 0.5]);
+		}
+		assert!(parse_stack_cast("u8->f16").is_err());
+	}
+
+	#[test]
+	fn checksums_of_cached_files() {
+		let dir = tempfile::tempdir().unwrap();
+		let (a, b) = (b"cached ".repeat(100), b"copied ".repeat(100));
+		write_zip(&dir.path().join("in.zip"), &[("a.txt", &a), ("b.bin", &b)]);
+		let args = ["@in.zip", "-o", "@out.zip", "--cache-dir", "@cache"];
+		let args = [&args[..], &["-r", "*.txt=deflated", "-r", "*.bin=stored"]].concat();
+		let args = [&args[..], &["--checksums", "blake3", "-f"]].concat();
+		let expected = format!(
+			"{}  a.txt\n{}  b.bin\n",
+			blake3::hash(&a).to_hex(),
+			blake3::hash(&b).to_hex()
+		);
+		for _reused in [false, true] {
+			rezip(&dir, &args).unwrap();
+			let sums = fs::read_to_string(dir.path().join("out.zip.blake3")).unwrap();
+			assert_eq!(sums, expected);
+			assert_eq!(fs::read_dir(dir.path().join("cache")).unwrap().count(), 1);
+		}
+	}
+
+	// Writes a ZIP archive of files deflated at level 1 and last modified on 1999-12-31 at 23:59:58.
+	fn write_deflated_zip(path: &Path, files: &[(&str, &[u8])]) {
+		let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+		let time = DateTime::from_date_and_time(1999, 12, 31, 23, 59, 58).unwrap();
+		let options = FileOptions::default()
+			.compression_method(CompressionMethod::Deflated)
+			.compression_level(Some(1))
+			.last_modified_time(time);
+		for (name, data) in files {
+			zip.start_file(*name, options).unwrap();
+			zip.write_all(data).unwrap();
+		}
+		zip.finish().unwrap();
+	}
+
+	#[test]
+	fn raw_copy_entry_timestamp_source() {
+		let dir = tempfile::tempdir().unwrap();
+		write_deflated_zip(&dir.path().join("in.zip"), &[("a.txt", &b"a".repeat(100))]);
+		let args = ["@in.zip", "-o", "@out.zip", "-r", "deflated", "-f"];
+		let args = [&args[..], &["--zip64", "never"]].concat();
+		for (source, time) in [
+			("source", (1999, 12, 31, 23, 59, 58)),
+			("fixed=2001-02-03T04:05:06Z", (2001, 2, 3, 4, 5, 6)),
+		] {
+			rezip(
+				&dir,
+				&[&args[..], &["--entry-timestamp-source", source]].concat(),
+			)
+			.unwrap();
+			let mut zip =
+				ZipArchive::new(fs::File::open(dir.path().join("out.zip")).unwrap()).unwrap();
+			assert_eq!(time_key(zip.by_index(0).unwrap().last_modified()), time);
+		}
+	}
+
+	#[test]
+	fn zip64_always_of_copied_files() {
+		let dir = tempfile::tempdir().unwrap();
+		write_deflated_zip(&dir.path().join("in.zip"), &[("a.txt", &b"a".repeat(100))]);
+		let args = ["@in.zip", "-o", "@out.zip", "-r", "deflated", "-f"];
+		for (mode, extra) in [("always", &[1, 0][..]), ("never", &[])] {
+			rezip(&dir, &[&args[..], &["--zip64", mode]].concat()).unwrap();
+			// Extra field of the local file header of the first file.
+			let zip = fs::read(dir.path().join("out.zip")).unwrap();
+			let length = usize::from(u16::from_le_bytes([zip[28], zip[29]]));
+			let start = 30 + "a.txt".len();
+			assert_eq!(&zip[start..start + length.min(2)], extra);
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn content_address_stacks_once() {
+		use std::os::unix::fs::PermissionsExt;
+		let dir = tempfile::tempdir().unwrap();
+		// Counts its runs by appending lines to a file next to it.
+		let script = dir.path().join("stack.sh");
+		fs::write(&script, "#!/bin/sh\necho >> \"$0.runs\"\ncat \"$@\"\n").unwrap();
+		fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+		write_zip(&dir.path().join("a.zip"), &[("x.txt", b"a\n")]);
+		write_zip(&dir.path().join("b.zip"), &[("x.txt", b"b\n")]);
+		let command = format!("*.txt={}", script.display());
+		let args = ["@a.zip", "@b.zip", "-o", "@out.zip", "--content-address"];
+		rezip(&dir, &[&args[..], &["--stack-cmd", &command]].concat()).unwrap();
+		assert_eq!(
+			fs::read_to_string(dir.path().join("stack.sh.runs")).unwrap(),
+			"\n"
+		);
+		let name = format!("{}.txt", blake3::hash(b"a\nb\n").to_hex());
+		assert_eq!(
+			read_zip(&dir.path().join("out.zip")),
+			[(name, b"a\nb\n".to_vec())]
+		);
+	}
+
+	#[test]
+	fn raw_copy_of_matching_method() {
+		// Reads the names, compressed data, and modification times of the files of a ZIP archive.
+		fn read_raw(path: &Path) -> Vec<(String, Vec<u8>, DateTime)> {
+			let mut zip = ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+			(0..zip.len())
+				.map(|index| {
+					let mut file = zip.by_index_raw(index).unwrap();
+					let mut data = Vec::new();
+					file.read_to_end(&mut data).unwrap();
+					(file.name().to_string(), data, file.last_modified())
+				})
+				.collect()
+		}
+		let dir = tempfile::tempdir().unwrap();
+		let data = (0..1000)
+			.map(|number| format!("{}\n", number))
+			.collect::<String>();
+		write_deflated_zip(&dir.path().join("in.zip"), &[("a.txt", data.as_bytes())]);
+		let args = ["@in.zip", "-o", "@out.zip", "-r", "deflated", "-f"];
+		let args = [&args[..], &["--rename", "a=b", "--checksums", "blake3"]].concat();
+		let (_name, input, time) = read_raw(&dir.path().join("in.zip")).remove(0);
+		for (mode, copied) in [("never", true), ("always", false)] {
+			rezip(&dir, &[&args[..], &["--zip64", mode]].concat()).unwrap();
+			let (name, output, output_time) = read_raw(&dir.path().join("out.zip")).remove(0);
+			assert_eq!(name, "b.txt");
+			assert_eq!(output == input, copied);
+			assert_eq!(time_key(output_time), time_key(time));
+			assert_eq!(
+				read_zip(&dir.path().join("out.zip")),
+				[(name, data.as_bytes().to_vec())]
+			);
+			let sums = fs::read_to_string(dir.path().join("out.zip.blake3")).unwrap();
+			assert_eq!(
+				sums,
+				format!("{}  b.txt\n", blake3::hash(data.as_bytes()).to_hex())
+			);
+		}
+	}
+}